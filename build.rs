@@ -5,11 +5,62 @@ use std::env;
 use std::fs::File;
 use std::path::Path;
 
+/// Extensions which are always generated, because the library uses them if they are supported.
+const GL_EXTENSIONS_: &'static [&'static str] = &["GL_KHR_debug", "GL_ARB_debug_output", "GL_KHR_robustness", "GL_ARB_robustness"];
+const GLES_EXTENSIONS_: &'static [&'static str] = &["GL_KHR_debug", "GL_KHR_robustness", "GL_EXT_robustness"];
+
+/// Whether the cargo feature `name` is enabled.
+fn feature_(name: &str) -> bool {
+    env::var_os(format!("CARGO_FEATURE_{}", name.to_uppercase())).is_some()
+}
+
+/// Get the api and version selected by the cargo features.
+/// Defaults to opengl 2.0, and the highest version is used if several features are enabled.
+fn api_and_version_() -> (Api, (u8, u8)) {
+    let gl = if feature_("gl45") {
+        Some((4, 5))
+    } else if feature_("gl33") {
+        Some((3, 3))
+    } else {
+        None
+    };
+    let gles = if feature_("gles3") {
+        Some((3, 0))
+    } else if feature_("gles2") {
+        Some((2, 0))
+    } else {
+        None
+    };
+    match (gl, gles) {
+        (Some(_), Some(_)) => panic!("the features for opengl and opengl es are mutually exclusive"),
+        (Some(version), None) => (Api::Gl, version),
+        (None, Some(version)) => (Api::Gles2, version),
+        (None, None) => (Api::Gl, (2, 0)),
+    }
+}
+
 fn main() {
     let dest = env::var("OUT_DIR").unwrap();
     let mut file = File::create(&Path::new(&dest).join("bindings.rs")).unwrap();
 
-    Registry::new(Api::Gl, (2, 0), Profile::Core, Fallbacks::All, [])
+    let (api, version) = api_and_version_();
+    let mut extensions: Vec<String> = match api {
+        Api::Gles2 => GLES_EXTENSIONS_,
+        _ => GL_EXTENSIONS_,
+    }.iter().map(|extension| extension.to_string()).collect();
+    println!("cargo:rerun-if-changed=build.rs");
+    // Additional extensions can be requested as a comma or whitespace separated list.
+    println!("cargo:rerun-if-env-changed=SCGL_EXTENSIONS");
+    if let Ok(extra_extensions) = env::var("SCGL_EXTENSIONS") {
+        for extension in extra_extensions.split(|c: char| c == ',' || c.is_whitespace()).filter(|extension| !extension.is_empty()) {
+            if !extensions.iter().any(|known| known == extension) {
+                extensions.push(extension.to_string());
+            }
+        }
+    }
+    let extensions: Vec<&str> = extensions.iter().map(|extension| &**extension).collect();
+
+    Registry::new(api, version, Profile::Core, Fallbacks::All, extensions)
         .write_bindings(StructGenerator, &mut file)
         .unwrap();
 }
@@ -1,40 +1,33 @@
 use std::os::raw::c_void;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, ATOMIC_BOOL_INIT, Ordering};
+use std::collections::{BTreeSet, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::process;
 
 use gl;
 use gl::Gl;
 use Error;
+use ObjectRegistry;
+use LiveObject;
+use ApiInfo;
+use ResetStatus;
+use SharingGroup;
 
 
-/// Whether an api exists.
-/// This is used to prevent two or more apis to exist at the same time.
-static API_EXISTS_: AtomicBool = ATOMIC_BOOL_INIT;
+/// The id of the next api.
+static NEXT_API_ID_: AtomicUsize = AtomicUsize::new(1);
 
-/// Tries to set API_EXISTS_ on construction (fails if it is already set).
-/// Clears API_EXISTS_ on destruction.
-struct ScopedApiExistsGuard_(());
-impl ScopedApiExistsGuard_ {
-    fn new() -> Result<Self, Error> {
-        if API_EXISTS_.swap(true, Ordering::AcqRel) {
-            Err(Error::ApiAlreadyExists)
-        } else {
-            Ok(ScopedApiExistsGuard_(()))
-        }
-    }
-}
-impl Drop for ScopedApiExistsGuard_ {
-    fn drop(&mut self) {
-        API_EXISTS_.store(false, Ordering::AcqRel);
-    }
-}
+/// Identity of an api, which is unique within the process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ApiId(usize);
 
 
 /// Backend of an api.
 /// An Api provides access to the procedure calls of an opengl api.
 /// It may be shared between multiple contexts.
 /// This trait will be used with dynamic dispatched only.
-pub unsafe trait ApiBackend: Sync + 'static {
+/// The api is shared by the contexts of all threads, so the backend has to be Send and Sync.
+pub unsafe trait ApiBackend: Send + Sync + 'static {
     /// Clear the current context.
     /// A check, whether the current context is cleared is not done before. So this optimization
     /// is expected to be done by the implementation.
@@ -51,32 +44,383 @@ pub unsafe trait MakeApiBackend {
 }
 
 
+/// Policy when the errors of opengl calls are checked using glGetError.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCheck {
+    /// Check after every call.
+    Always,
+    /// Check after every call, but only if debug assertions are enabled.
+    DebugOnly,
+    /// Check only once per frame, when CurrentContext::check_frame_errors is called.
+    PerFrame,
+    /// Never check.
+    Never,
+}
+impl ErrorCheck {
+    /// Whether errors are checked after every call.
+    pub fn checks_calls(&self) -> bool {
+        match *self {
+            ErrorCheck::Always => true,
+            ErrorCheck::DebugOnly => cfg!(debug_assertions),
+            ErrorCheck::PerFrame | ErrorCheck::Never => false,
+        }
+    }
+    /// Whether errors are checked at the end of a frame.
+    pub fn checks_frames(&self) -> bool {
+        match *self {
+            ErrorCheck::Always | ErrorCheck::PerFrame => true,
+            ErrorCheck::DebugOnly => cfg!(debug_assertions),
+            ErrorCheck::Never => false,
+        }
+    }
+    fn to_usize_(self) -> usize {
+        match self {
+            ErrorCheck::Always => 0,
+            ErrorCheck::DebugOnly => 1,
+            ErrorCheck::PerFrame => 2,
+            ErrorCheck::Never => 3,
+        }
+    }
+    fn from_usize_(val: usize) -> Self {
+        match val {
+            0 => ErrorCheck::Always,
+            1 => ErrorCheck::DebugOnly,
+            2 => ErrorCheck::PerFrame,
+            _ => ErrorCheck::Never,
+        }
+    }
+}
+
+
+/// Strategy of make_current against changes of the current context, which are not done through
+/// this library, for example by a host application or a gui toolkit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExternalChanges {
+    /// Trust that the current context is only changed through this library. Making the context
+    /// current again does not call the backend and keeps the state cache.
+    Trust,
+    /// Check whether the context is still current using the backend. If not, it is made current
+    /// again and the state cache is cleared.
+    CheckCurrent,
+    /// Like CheckCurrent, but additionally query the bound objects using glGetIntegerv to rebuild
+    /// the state cache, because the bindings may have been changed as well.
+    Resync,
+}
+impl ExternalChanges {
+    fn to_usize_(self) -> usize {
+        match self {
+            ExternalChanges::Trust => 0,
+            ExternalChanges::CheckCurrent => 1,
+            ExternalChanges::Resync => 2,
+        }
+    }
+    fn from_usize_(val: usize) -> Self {
+        match val {
+            0 => ExternalChanges::Trust,
+            1 => ExternalChanges::CheckCurrent,
+            _ => ExternalChanges::Resync,
+        }
+    }
+}
+
+
+/// Handling of errors which can not be returned, because they occur while dropping a current
+/// context or an object.
+#[derive(Clone, Copy)]
+pub enum ErrorHook {
+    /// Report the error through the log crate if the log feature is enabled, and to stderr
+    /// otherwise.
+    Log,
+    /// Report the error to stderr and abort the process.
+    Abort,
+    /// Ignore the error.
+    Ignore,
+    /// Call the function with the operation which failed and the error.
+    Custom(fn(&'static str, &Error)),
+}
+
+#[cfg(feature = "log")]
+fn log_error_(operation: &'static str, error: &Error) {
+    error!("scgl: {} failed: {}", operation, error);
+}
+
+#[cfg(not(feature = "log"))]
+fn log_error_(operation: &'static str, error: &Error) {
+    eprintln!("scgl: {} failed: {}", operation, error);
+}
+
+
+/// Kind of the opengl api driven by an api.
+/// It selects the #version header of shader bodies and whether ARB_debug_output may be used.
+/// The entry points are selected by the generated bindings, which fall back to the suffixed
+/// variants of extensions, like glGenVertexArraysOES, if the core procedure is not loaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiKind {
+    /// Desktop opengl.
+    Gl,
+    /// Opengl es.
+    Gles,
+}
+impl ApiKind {
+    /// Get the kind the bindings were generated for by the cargo features.
+    pub fn of_bindings() -> Self {
+        if cfg!(any(feature = "gles2", feature = "gles3")) {
+            ApiKind::Gles
+        } else {
+            ApiKind::Gl
+        }
+    }
+    /// Get the header prepended to shader bodies.
+    /// It contains the #version directive matching the generated bindings, and a default float
+    /// precision for opengl es.
+    pub fn shader_header(&self) -> &'static str {
+        match *self {
+            ApiKind::Gl if cfg!(feature = "gl45") => "#version 450 core\n",
+            ApiKind::Gl if cfg!(feature = "gl33") => "#version 330 core\n",
+            ApiKind::Gl => "#version 110\n",
+            ApiKind::Gles if cfg!(feature = "gles3") => "#version 300 es\nprecision mediump float;\n",
+            ApiKind::Gles => "#version 100\nprecision mediump float;\n",
+        }
+    }
+}
+
+
+/// Whether the procedure `name` has a vendor suffix like ARB or EXT.
+fn has_vendor_suffix_(name: &str) -> bool {
+    let suffix_len = name.bytes().rev().take_while(|byte| byte.is_ascii_uppercase()).count();
+    suffix_len >= 2 && suffix_len < name.len()
+}
+
+/// Get the name of a procedure without its vendor suffix.
+fn base_name_(name: &str) -> &str {
+    if has_vendor_suffix_(name) {
+        name.trim_end_matches(|c: char| c.is_ascii_uppercase())
+    } else {
+        name
+    }
+}
+
+/// The procedures of the generated bindings which could not be loaded.
+/// A procedure counts as loaded if it or one of its fallbacks resolved.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    /// The missing procedures without vendor suffix, sorted by name.
+    /// These are core procedures, or procedures of extensions like KHR_debug which have no suffix.
+    pub missing_core: Vec<String>,
+    /// The missing procedures with vendor suffix, sorted by name.
+    pub missing_extension: Vec<String>,
+}
+impl LoadReport {
+    /// Create the report from the procedures requested while loading and whether they resolved.
+    fn new_(requested: &[(String, bool)]) -> Self {
+        let loaded_base_names: HashSet<&str> = requested.iter()
+            .filter(|&&(_, loaded)| loaded)
+            .map(|&(ref name, _)| base_name_(name))
+            .collect();
+        let missing: BTreeSet<&str> = requested.iter()
+            .filter(|&&(ref name, loaded)| !loaded && !loaded_base_names.contains(base_name_(name)))
+            .map(|&(ref name, _)| &**name)
+            .collect();
+        LoadReport {
+            missing_core: missing.iter().filter(|name| !has_vendor_suffix_(name)).map(|name| name.to_string()).collect(),
+            missing_extension: missing.iter().filter(|name| has_vendor_suffix_(name)).map(|name| name.to_string()).collect(),
+        }
+    }
+    /// Whether every procedure was loaded.
+    pub fn is_complete(&self) -> bool {
+        self.missing_core.is_empty() && self.missing_extension.is_empty()
+    }
+}
+
+
+/// Builder of an api.
+pub struct ApiBuilder {
+    error_check_: ErrorCheck,
+    external_changes_: ExternalChanges,
+    error_hook_: ErrorHook,
+    kind_: ApiKind,
+    require_core_functions_: bool,
+}
+impl ApiBuilder {
+    /// Create a builder with the default settings.
+    pub fn new() -> Self {
+        ApiBuilder {
+            error_check_: ErrorCheck::DebugOnly,
+            external_changes_: ExternalChanges::CheckCurrent,
+            error_hook_: ErrorHook::Log,
+            kind_: ApiKind::of_bindings(),
+            require_core_functions_: false,
+        }
+    }
+    /// Set the error checking policy. Defaults to ErrorCheck::DebugOnly.
+    pub fn error_check(mut self, error_check: ErrorCheck) -> Self {
+        self.error_check_ = error_check;
+        self
+    }
+    /// Set the strategy against external changes of the current context. Defaults to
+    /// ExternalChanges::CheckCurrent.
+    pub fn external_changes(mut self, external_changes: ExternalChanges) -> Self {
+        self.external_changes_ = external_changes;
+        self
+    }
+    /// Set the handling of errors while dropping. Defaults to ErrorHook::Log.
+    pub fn error_hook(mut self, error_hook: ErrorHook) -> Self {
+        self.error_hook_ = error_hook;
+        self
+    }
+    /// Set the kind of the api. Defaults to ApiKind::of_bindings(). The opengl es 2.0 subset of
+    /// the opengl bindings may be used to drive opengl es.
+    pub fn kind(mut self, kind: ApiKind) -> Self {
+        self.kind_ = kind;
+        self
+    }
+    /// Whether building fails with Error::MissingFunctions if a procedure of
+    /// LoadReport::missing_core could not be loaded. Defaults to false.
+    pub fn require_core_functions(mut self, require_core_functions: bool) -> Self {
+        self.require_core_functions_ = require_core_functions;
+        self
+    }
+    /// Build the api.
+    pub fn build<MAB: MakeApiBackend>(self, mut mab: MAB) -> Result<Api, Error> {
+        unsafe {
+            let mut requested = Vec::new();
+            let gl = gl::Gl::load_with(|name| {
+                let address = mab.get_proc_address(name);
+                requested.push((name.to_string(), !address.is_null()));
+                address
+            });
+            let load_report = LoadReport::new_(&requested);
+            if self.require_core_functions_ && !load_report.missing_core.is_empty() {
+                return Err(Error::MissingFunctions(load_report.missing_core));
+            }
+            Ok(Api {
+                id_: ApiId(NEXT_API_ID_.fetch_add(1, Ordering::Relaxed)),
+                gl_: gl,
+                backend_: mab.into_backend(),
+                error_check_: AtomicUsize::new(self.error_check_.to_usize_()),
+                external_changes_: AtomicUsize::new(self.external_changes_.to_usize_()),
+                error_hook_: Mutex::new(self.error_hook_),
+                reset_callback_: Mutex::new(None),
+                kind_: self.kind_,
+                info_: OnceLock::new(),
+                load_report_: load_report,
+                objects_: ObjectRegistry::new(),
+            })
+        }
+    }
+}
+
+
 /// Frontend for an api backend.
 /// An Api provides access to the procedure calls of an opengl api.
-/// There may only exists at most one api at time.
-/// But note, there may exists more than one api backends at time.
+/// Several apis may exist at the same time, for example one loaded from a window and one from
+/// a headless backend. Contexts and objects are tied to the api which created them.
 /// Instances of this type will only be used by this library wrapped in an Arc<Api>.
 /// Each context contains a strong reference to the api.
 /// It is ensured that the Backend will be dropped when the api is dropped.
 pub struct Api {
+    id_: ApiId,
     gl_: gl::Gl,
     backend_: Box<ApiBackend>,
-    scoped_api_exists_guard_: ScopedApiExistsGuard_,
+    error_check_: AtomicUsize,
+    external_changes_: AtomicUsize,
+    error_hook_: Mutex<ErrorHook>,
+    reset_callback_: Mutex<Option<Arc<Fn(&Arc<SharingGroup>, ResetStatus) + Send + Sync>>>,
+    kind_: ApiKind,
+    info_: OnceLock<ApiInfo>,
+    load_report_: LoadReport,
+    objects_: ObjectRegistry,
 }
 impl Api {
-    pub fn new<MAB: MakeApiBackend>(mut mab: MAB) -> Result<Self, Error> {
-        let scoped_api_exists_guard = try!(ScopedApiExistsGuard_::new());
-        unsafe {
-            let gl = gl::Gl::load_with(|s| mab.get_proc_address(s));
-            Ok(Api {
-                gl_: gl,
-                backend_: mab.into_backend(),
-                scoped_api_exists_guard_: scoped_api_exists_guard,
-            })
-        }
+    /// Create an api with the default settings of ApiBuilder.
+    pub fn new<MAB: MakeApiBackend>(mab: MAB) -> Result<Self, Error> {
+        ApiBuilder::new().build(mab)
+    }
+    /// Get the identity of the api.
+    pub fn id(&self) -> ApiId {
+        self.id_
     }
     // Get the backend.
     pub fn backend(&self) -> &ApiBackend { &*self.backend_ }
     // Get the opengl calls.
     pub fn gl(&self) -> &Gl { &self.gl_ }
+    /// Get the kind of the api.
+    pub fn kind(&self) -> ApiKind {
+        self.kind_
+    }
+    /// Get the procedures which could not be loaded.
+    pub fn load_report(&self) -> &LoadReport {
+        &self.load_report_
+    }
+    /// Get the information about the implementation.
+    /// It is queried when a context of the api is made current the first time, and None before.
+    pub fn info(&self) -> Option<&ApiInfo> {
+        self.info_.get()
+    }
+    /// Set the information, unless it is already set.
+    pub(crate) fn init_info(&self, info: ApiInfo) {
+        let _ = self.info_.set(info);
+    }
+    /// Get the error checking policy.
+    pub fn error_check(&self) -> ErrorCheck {
+        ErrorCheck::from_usize_(self.error_check_.load(Ordering::Relaxed))
+    }
+    /// Change the error checking policy at runtime.
+    pub fn set_error_check(&self, error_check: ErrorCheck) {
+        self.error_check_.store(error_check.to_usize_(), Ordering::Relaxed);
+    }
+    /// Get the strategy against external changes of the current context.
+    pub fn external_changes(&self) -> ExternalChanges {
+        ExternalChanges::from_usize_(self.external_changes_.load(Ordering::Relaxed))
+    }
+    /// Change the strategy against external changes of the current context at runtime.
+    pub fn set_external_changes(&self, external_changes: ExternalChanges) {
+        self.external_changes_.store(external_changes.to_usize_(), Ordering::Relaxed);
+    }
+    /// Get the handling of errors while dropping.
+    pub fn error_hook(&self) -> ErrorHook {
+        *self.error_hook_.lock().unwrap_or_else(|error| error.into_inner())
+    }
+    /// Change the handling of errors while dropping at runtime.
+    pub fn set_error_hook(&self, error_hook: ErrorHook) {
+        *self.error_hook_.lock().unwrap_or_else(|error| error.into_inner()) = error_hook;
+    }
+    /// Pass an error, which occurred while dropping, to the error hook.
+    pub(crate) fn report_drop_error(&self, operation: &'static str, error: Error) {
+        match self.error_hook() {
+            ErrorHook::Log => log_error_(operation, &error),
+            ErrorHook::Abort => {
+                eprintln!("scgl: {} failed: {}", operation, error);
+                process::abort();
+            },
+            ErrorHook::Ignore => (),
+            ErrorHook::Custom(hook) => hook(operation, &error),
+        }
+    }
+    /// Set the function, which is called once for each sharing group lost by a reset of the
+    /// context. The application may recreate its objects in a new sharing group.
+    pub fn set_reset_callback<F: Fn(&Arc<SharingGroup>, ResetStatus) + Send + Sync + 'static>(&self, reset_callback: F) {
+        *self.reset_callback_.lock().unwrap_or_else(|error| error.into_inner()) = Some(Arc::new(reset_callback));
+    }
+    /// Call the reset callback. The lock is not held during the call, so the callback may set
+    /// another one.
+    pub(crate) fn notify_reset(&self, sharing_group: &Arc<SharingGroup>, reset_status: ResetStatus) {
+        let reset_callback = self.reset_callback_.lock().unwrap_or_else(|error| error.into_inner()).clone();
+        if let Some(reset_callback) = reset_callback {
+            reset_callback(sharing_group, reset_status);
+        }
+    }
+    /// Get the registry of the live objects.
+    pub(crate) fn objects(&self) -> &ObjectRegistry {
+        &self.objects_
+    }
+    /// Get the objects which were created and not deleted yet, including objects which were
+    /// split into their handle or forgotten.
+    pub fn live_objects(&self) -> Vec<LiveObject> {
+        self.objects_.live_objects()
+    }
 }
+// The procedure pointers are not changed after loading, and may be called in every thread in
+// which a context of the api is current.
+unsafe impl Send for Api {}
+unsafe impl Sync for Api {}
@@ -3,15 +3,23 @@ use std::sync::Arc;
 use std::os::raw::c_void;
 use std::mem::{forget, size_of};
 use std::marker::PhantomData;
+use std::fmt;
 
 use gl;
 use gl::types::GLuint;
 
 use Error;
+use ObjectKind;
 
 use Api;
 use CurrentContext;
 use StateCache;
+use SharingGroupId;
+use SharingGroup;
+#[cfg(feature = "gl45")]
+use HasBufferStorage;
+#[cfg(feature = "gl45")]
+use VersionedContext;
 
 /// Target specific enumeration values and implementation state change optimization for buffers.
 pub unsafe trait BufferTarget {
@@ -24,55 +32,100 @@ pub unsafe trait BufferTarget {
 }
 
 /// A buffer which implements Sync and Send.
+/// Dropping it without converting it to a current buffer leaks the opengl buffer, which is
+/// reported as a live object of its sharing group.
 pub struct AsyncBuffer<Target: BufferTarget> {
     gl_handle_: GLuint,
-    api_: Arc<Api>,
+    sharing_group_: Arc<SharingGroup>,
+    label_: Option<String>,
     phantom_target_: PhantomData<Target>,
 }
 impl<Target: BufferTarget> AsyncBuffer<Target> {
     /// Get the api
     pub fn api(&self) -> &Arc<Api> {
-        &self.api_
+        self.sharing_group_.api()
+    }
+    /// Get the sharing group.
+    pub fn sharing_group(&self) -> &Arc<SharingGroup> {
+        &self.sharing_group_
     }
     /// Get the opengl handle.
     pub fn gl_handle(&self) -> GLuint {
         self.gl_handle_
     }
-    /// Unsafe split the async buffer into the handle and the api.
-    pub unsafe fn split(mut self) -> (GLuint, Arc<Api>) {
-        let res = (self.gl_handle_, self.api().clone());
+    /// Get the label set by set_label, which is added to the errors of the object.
+    pub fn label(&self) -> Option<&str> {
+        self.label_.as_ref().map(|label| &**label)
+    }
+    /// Set the label. It is passed to glObjectLabel when the buffer is converted to a current
+    /// buffer.
+    pub fn set_label(&mut self, label: &str) {
+        self.api().objects().set_label(self.sharing_group_.id(), ObjectKind::Buffer, self.gl_handle(), label);
+        self.label_ = Some(label.to_string());
+    }
+    /// Unsafe split the async buffer into the handle and the sharing group.
+    /// The buffer stays registered as a live object until it is deleted.
+    pub unsafe fn split(mut self) -> (GLuint, Arc<SharingGroup>) {
+        let res = (self.gl_handle_, self.sharing_group_.clone());
         self.gl_handle_ = 0;
         res
     }
     /// Convert to the async buffer to a current buffer.
-    pub fn to_current(self, current_context: Rc<CurrentContext>) -> CurrentBuffer<Target> {
+    /// Returns Error::ApiMismatch if the current context belongs to another api,
+    /// Error::WrongSharingGroup if it belongs to another sharing group and Error::ContextLost if
+    /// the sharing group was lost. The async buffer is returned with the error, so the conversion
+    /// can be retried with another context.
+    pub fn to_current(mut self, current_context: Rc<CurrentContext>) -> Result<CurrentBuffer<Target>, (AsyncBuffer<Target>, Error)> {
+        let res = if self.sharing_group_.is_lost() {
+            Err(Error::ContextLost)
+        } else if current_context.api().id() != self.api().id() {
+            Err(Error::ApiMismatch)
+        } else {
+            current_context.check_sharing_group(self.sharing_group_.id()).and_then(|()| match self.label_ {
+                Some(ref label) => current_context.object_label(gl::BUFFER, self.gl_handle_, label),
+                None => Ok(()),
+            })
+        };
+        if let Err(error) = res {
+            let error = error.with_object(ObjectKind::Buffer, self.gl_handle(), self.label());
+            return Err((self, error));
+        }
         unsafe {
-            let (gl_handle, api) = self.split();
+            let label = self.label_.take();
+            let (gl_handle, _) = self.split();
             Target::mark_bound(current_context.state_cache(), 0);
-            CurrentBuffer::from_gl_handle(gl_handle, current_context)
+            let mut current_buffer = CurrentBuffer::from_gl_handle(gl_handle, current_context);
+            current_buffer.label_ = label;
+            Ok(current_buffer)
         }
     }
     /// Unsafe create async buffer from gl handle.
-    pub unsafe fn from_gl_handle(gl_handle: GLuint, api: Arc<Api>) -> Self {
+    pub unsafe fn from_gl_handle(gl_handle: GLuint, sharing_group: Arc<SharingGroup>) -> Self {
+        sharing_group.api().objects().register(sharing_group.id(), ObjectKind::Buffer, gl_handle);
         AsyncBuffer {
             gl_handle_: gl_handle,
-            api_: api,
+            sharing_group_: sharing_group,
+            label_: None,
             phantom_target_: PhantomData,
         }
     }
 }
-impl<Target: BufferTarget> Drop for AsyncBuffer<Target> {
-    fn drop(&mut self) {
-        if self.gl_handle() == 0 {
-            panic!("Drop of AsyncBuffer")
-        }
+impl<Target: BufferTarget> fmt::Debug for AsyncBuffer<Target> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AsyncBuffer")
+            .field("gl_handle", &self.gl_handle_)
+            .field("sharing_group_id", &self.sharing_group_.id())
+            .field("label", &self.label_)
+            .finish()
     }
 }
-
 /// A buffer which does not implement Sync or Send, but may be actually used.
+/// It may only be used while a context of its sharing group is current.
 pub struct CurrentBuffer<Target: BufferTarget> {
     gl_handle_: GLuint,
     current_context_: Rc<CurrentContext>,
+    sharing_group_id_: SharingGroupId,
+    label_: Option<String>,
     phantom_target_: PhantomData<Target>,
 }
 impl<Target: BufferTarget> CurrentBuffer<Target> {
@@ -84,29 +137,74 @@ impl<Target: BufferTarget> CurrentBuffer<Target> {
     pub fn current_context(&self) -> &Rc<CurrentContext> {
         &self.current_context_
     }
+    /// Get the id of the sharing group.
+    pub fn sharing_group_id(&self) -> SharingGroupId {
+        self.sharing_group_id_
+    }
+    /// Get the label set by set_label, which is added to the errors of the object.
+    pub fn label(&self) -> Option<&str> {
+        self.label_.as_ref().map(|label| &**label)
+    }
+    /// Set the label. It is passed to glObjectLabel if KHR_debug is supported.
+    pub fn set_label(&mut self, label: &str) -> Result<(), Error> {
+        try!(self.check_sharing_group_(self.current_context()));
+        self.current_context().api().objects().set_label(self.sharing_group_id(), ObjectKind::Buffer, self.gl_handle(), label);
+        self.label_ = Some(label.to_string());
+        try!(self.current_context().object_label(gl::BUFFER, self.gl_handle(), label).map_err(|error| error.with_object(ObjectKind::Buffer, self.gl_handle(), self.label())));
+        Ok(())
+    }
+    /// Check the errors of an opengl call with the buffer.
+    fn check_errors_(&self, current_context: &CurrentContext, call: &'static str) -> Result<(), Error> {
+        current_context.check_errors(call).map_err(|error| error.with_object(ObjectKind::Buffer, self.gl_handle(), self.label()))
+    }
+    /// Check that the current context is part of the sharing group of the buffer.
+    fn check_sharing_group_(&self, current_context: &CurrentContext) -> Result<(), Error> {
+        current_context.check_sharing_group(self.sharing_group_id()).map_err(|error| error.with_object(ObjectKind::Buffer, self.gl_handle(), self.label()))
+    }
     /// Bind the buffer.
-    fn bind(&self, current_context: &CurrentContext) -> Result<(), Error> {
+    pub fn bind(&self, current_context: &CurrentContext) -> Result<(), Error> {
+        try!(self.check_sharing_group_(current_context));
         unsafe {
             if Target::marked_bound(current_context.state_cache()) != self.gl_handle() {
-                // TODO: Error checking.
+                try!(current_context.require_functions(&[("glBindBuffer", &current_context.gl().BindBuffer)]));
                 current_context.gl().BindBuffer(Target::enum_val(), self.gl_handle());
+                try!(self.check_errors_(current_context, "glBindBuffer"));
                 Target::mark_bound(current_context.state_cache(), self.gl_handle());
             }
             Ok(())
         }
     }
     /// Set the data of the buffer.
-    fn set_data<DataElem: Copy>(&self, current_context: &CurrentContext, data: &[DataElem]) -> Result<(), Error> {
+    pub fn set_data<DataElem: Copy>(&self, current_context: &CurrentContext, data: &[DataElem]) -> Result<(), Error> {
         unsafe {
             try!(self.bind(current_context));
+            try!(current_context.require_functions(&[("glBufferData", &current_context.gl().BufferData)]));
             let data_len = (size_of::<DataElem>() * data.len()) as isize;
             let data_ptr = data as *const [DataElem] as *const c_void;
-            // TODO: Error checking.
             current_context.gl().BufferData(Target::enum_val(), data_len, data_ptr, gl::STATIC_DRAW);
+            try!(self.check_errors_(current_context, "glBufferData"));
+            Ok(())
+        }
+    }
+    /// Create immutable storage for the buffer using glBufferStorage, initialized with `data`.
+    /// `flags` is a combination of the GL_DYNAMIC_STORAGE_BIT and GL_MAP_* bits.
+    /// Returns Error::WrongContext if another context was made current since the version was
+    /// checked.
+    #[cfg(feature = "gl45")]
+    pub fn set_storage<V: HasBufferStorage, DataElem: Copy>(&self, versioned_context: &VersionedContext<V>, data: &[DataElem], flags: gl::types::GLbitfield) -> Result<(), Error> {
+        try!(versioned_context.check_context().map_err(|error| error.with_object(ObjectKind::Buffer, self.gl_handle(), self.label())));
+        unsafe {
+            try!(self.bind(versioned_context));
+            try!(versioned_context.require_functions(&[("glBufferStorage", &versioned_context.gl().BufferStorage)]));
+            let data_len = (size_of::<DataElem>() * data.len()) as isize;
+            let data_ptr = data as *const [DataElem] as *const c_void;
+            versioned_context.gl().BufferStorage(Target::enum_val(), data_len, data_ptr, flags);
+            try!(self.check_errors_(versioned_context, "glBufferStorage"));
             Ok(())
         }
     }
     /// Unsafe split the current buffer into the handle and the current context.
+    /// The buffer stays registered as a live object until it is deleted.
     pub unsafe fn split(mut self) -> (GLuint, Rc<CurrentContext>) {
         let res = (self.gl_handle_, self.current_context_.clone());
         if Target::marked_bound(self.current_context().state_cache()) == self.gl_handle() {
@@ -115,26 +213,51 @@ impl<Target: BufferTarget> CurrentBuffer<Target> {
         self.gl_handle_ = 0;
         res
     }
+    /// Convert the current buffer to an async buffer, which may be sent to another thread.
+    /// glFinish is called, so the commands modifying the buffer are completed before a context of
+    /// another thread uses it.
+    /// Returns Error::WrongSharingGroup if a context of another sharing group is current.
+    pub fn into_async(self) -> Result<AsyncBuffer<Target>, Error> {
+        try!(self.check_sharing_group_(self.current_context()));
+        unsafe {
+            try!(self.current_context().require_functions(&[("glFinish", &self.current_context().gl().Finish)]));
+            self.current_context().gl().Finish();
+            try!(self.check_errors_(self.current_context(), "glFinish"));
+            self.to_async()
+        }
+    }
     /// Unsafe convert to the current buffer to an async buffer. It is unsafe because glFinish has
     /// to be called before.
-    pub unsafe fn to_async(self) -> AsyncBuffer<Target> {
+    /// Returns Error::WrongSharingGroup if a context of another sharing group is current.
+    pub unsafe fn to_async(mut self) -> Result<AsyncBuffer<Target>, Error> {
+        try!(self.check_sharing_group_(self.current_context()));
+        let label = self.label_.take();
         let (gl_handle, current_context) = self.split();
-        AsyncBuffer::from_gl_handle(gl_handle, current_context.api().clone())
+        let mut async_buffer = AsyncBuffer::from_gl_handle(gl_handle, current_context.sharing_group().clone());
+        async_buffer.label_ = label;
+        Ok(async_buffer)
     }
     /// Unsafe create current buffer from gl handle.
+    /// The buffer belongs to the sharing group of the current context.
     pub unsafe fn from_gl_handle(gl_handle: GLuint, current_context: Rc<CurrentContext>) -> Self {
+        let sharing_group_id = current_context.sharing_group().id();
+        current_context.api().objects().register(sharing_group_id, ObjectKind::Buffer, gl_handle);
         CurrentBuffer {
             gl_handle_: gl_handle,
             current_context_: current_context,
+            sharing_group_id_: sharing_group_id,
+            label_: None,
             phantom_target_: PhantomData,
         }
     }
     /// Create a new current buffer.
-    pub fn create(current_context: Rc<CurrentContext>, state_cache: &mut StateCache) -> Result<Self, Error> {
+    pub fn create(current_context: Rc<CurrentContext>) -> Result<Self, Error> {
+        try!(current_context.check_not_lost());
         unsafe {
+            try!(current_context.require_functions(&[("glGenBuffers", &current_context.gl().GenBuffers), ("glDeleteBuffers", &current_context.gl().DeleteBuffers)]));
             let mut gl_handle: GLuint = 0;
-            // TODO: Error checking.
             current_context.gl().GenBuffers(1, &mut gl_handle as *mut GLuint);
+            try!(current_context.check_errors("glGenBuffers").map_err(|error| error.with_object(ObjectKind::Buffer, gl_handle, None)));
             Ok(Self::from_gl_handle(gl_handle, current_context))
         }
     }
@@ -142,12 +265,18 @@ impl<Target: BufferTarget> CurrentBuffer<Target> {
 impl<Target: BufferTarget> Drop for CurrentBuffer<Target> {
     fn drop(&mut self) {
         unsafe {
-            if self.gl_handle() != 0 {
+            // The buffer can not be deleted while a context of another sharing group is current,
+            // so it stays registered and is reported as leak.
+            if self.gl_handle() != 0 && self.current_context().gl().DeleteBuffers.is_loaded() && self.check_sharing_group_(self.current_context()).is_ok() {
                 if Target::marked_bound(self.current_context().state_cache()) == self.gl_handle() {
                     Target::mark_bound(self.current_context().state_cache(), 0);
                 }
-                // TODO: Error checking.
                 self.current_context().gl().DeleteBuffers(1, &self.gl_handle() as *const GLuint);
+                // Errors can not be propagated out of drop, so they are passed to the error hook.
+                if let Err(error) = self.current_context().check_errors("glDeleteBuffers") {
+                    self.current_context().api().report_drop_error("glDeleteBuffers", error);
+                }
+                self.current_context().api().objects().unregister(self.sharing_group_id(), ObjectKind::Buffer, self.gl_handle());
             }
         }
     }
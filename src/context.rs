@@ -1,8 +1,21 @@
 use Error;
 use Api;
+use SharingGroup;
+use DebugCallback;
+use reset_debug_message_callback;
+use restore_current_context;
 
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::rc::Rc;
+use std::cell::RefCell;
+
+/// The id of the next context.
+static NEXT_CONTEXT_ID_: AtomicUsize = AtomicUsize::new(1);
+
+/// Identity of a context, which is unique within the process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ContextId(usize);
 
 /// A context backend used by the api backend.
 pub unsafe trait ContextBackend: 'static {
@@ -16,18 +29,63 @@ pub unsafe trait ContextBackend: 'static {
 
 /// Context used by the api.
 pub struct Context {
-    api_: Arc<Api>,
+    id_: ContextId,
     backend_: Box<ContextBackend>,
+    /// The callback installed for the debug output. It is declared after the backend, so it is
+    /// dropped after the opengl context could call it the last time.
+    debug_callback_: RefCell<Option<Box<Box<DebugCallback>>>>,
+    /// The sharing group. It is declared after the backend, so leaks are reported after the
+    /// opengl context is destroyed.
+    sharing_group_: Arc<SharingGroup>,
 }
 impl Context {
+    /// Create a context with a sharing group of its own.
+    /// It is unsafe, because the backend has to be a context of the api.
     pub unsafe fn new(api: Arc<Api>, backend: Box<ContextBackend>) -> Self {
+        Self::new_in_sharing_group(SharingGroup::new_implicit(api), backend)
+    }
+    /// Create a context of a sharing group.
+    /// It is unsafe, because the backend has to be a context sharing its objects with the other
+    /// contexts of the group.
+    pub unsafe fn new_in_sharing_group(sharing_group: Arc<SharingGroup>, backend: Box<ContextBackend>) -> Self {
         Context {
-            api_: api,
+            id_: ContextId(NEXT_CONTEXT_ID_.fetch_add(1, Ordering::Relaxed)),
             backend_: backend,
+            debug_callback_: RefCell::new(None),
+            sharing_group_: sharing_group,
         }
     }
+    /// Get the id.
+    pub fn id(&self) -> ContextId { self.id_ }
     /// Get the api.
-    pub fn api(&self) -> &Arc<Api> { &self.api_ }
+    pub fn api(&self) -> &Arc<Api> { self.sharing_group_.api() }
+    /// Get the sharing group.
+    pub fn sharing_group(&self) -> &Arc<SharingGroup> { &self.sharing_group_ }
     /// Get the backend.
     pub fn backend(&self) -> &ContextBackend { &*self.backend_ }
+    /// Get the callback installed for the debug output.
+    pub(crate) fn debug_callback(&self) -> &RefCell<Option<Box<Box<DebugCallback>>>> { &self.debug_callback_ }
+}
+impl Drop for Context {
+    fn drop(&mut self) {
+        if self.debug_callback_.borrow().is_none() {
+            return;
+        }
+        // The opengl context may outlive the backend, for example if a window is still
+        // referenced, so the callback is removed while the context is current.
+        if let Err(error) = unsafe { self.remove_debug_callback_() } {
+            self.api().report_drop_error("removing the debug callback", error);
+        }
+    }
+}
+impl Context {
+    unsafe fn remove_debug_callback_(&self) -> Result<(), Error> {
+        if self.backend_.is_current() {
+            reset_debug_message_callback(self.api().gl());
+            return Ok(());
+        }
+        try!(self.backend_.make_current());
+        reset_debug_message_callback(self.api().gl());
+        restore_current_context(self.api())
+    }
 }
@@ -0,0 +1,90 @@
+use ApiKind;
+use Version;
+
+/// Profile of a context.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContextProfile {
+    /// Desktop opengl without the deprecated procedures. Only versions from 3.2 on have profiles.
+    Core,
+    /// Desktop opengl including the deprecated procedures.
+    Compatibility,
+    /// Opengl es.
+    Es,
+}
+
+/// Attributes of a context, which the backends turn into their native attributes when creating
+/// a context. So the same context can be requested from every backend.
+/// Backends return an error if an attribute is not supported, unless it is documented to be
+/// ignored by the backend.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContextConfig {
+    /// The minimal opengl or opengl es version.
+    pub version: Version,
+    /// The profile.
+    pub profile: ContextProfile,
+    /// Whether a debug context is created, which is required for the debug output of some
+    /// implementations.
+    pub debug: bool,
+    /// Whether the deprecated procedures are removed. Only used for desktop opengl.
+    pub forward_compatible: bool,
+    /// Whether a robust context is created, which loses the context on reset, see
+    /// CurrentContext::reset_status.
+    pub robust: bool,
+    /// Whether the default framebuffer is sRGB capable.
+    pub srgb: bool,
+    /// The minimal number of bits of the depth buffer.
+    pub depth_bits: u8,
+    /// The minimal number of bits of the stencil buffer.
+    pub stencil_bits: u8,
+    /// The number of samples for multisampling, or 0 to disable it.
+    pub samples: u8,
+    /// Whether a context without error checks is created using KHR_no_error. glGetError only
+    /// returns GL_NO_ERROR or GL_OUT_OF_MEMORY then, so ErrorCheck::Never should be used as well.
+    pub no_error: bool,
+}
+impl ContextConfig {
+    /// Get the config matching the bindings generated by the cargo features. It requests the
+    /// version of the bindings with the core profile for desktop opengl from 3.3 on, and the
+    /// compatibility profile for opengl 2.0.
+    pub fn of_bindings() -> Self {
+        let (version, profile) = if cfg!(feature = "gles3") {
+            (Version::new(3, 0), ContextProfile::Es)
+        } else if cfg!(feature = "gles2") {
+            (Version::new(2, 0), ContextProfile::Es)
+        } else if cfg!(feature = "gl45") {
+            (Version::new(4, 5), ContextProfile::Core)
+        } else if cfg!(feature = "gl33") {
+            (Version::new(3, 3), ContextProfile::Core)
+        } else {
+            (Version::new(2, 0), ContextProfile::Compatibility)
+        };
+        ContextConfig {
+            version: version,
+            profile: profile,
+            debug: false,
+            forward_compatible: false,
+            robust: false,
+            srgb: false,
+            depth_bits: 24,
+            stencil_bits: 8,
+            samples: 0,
+            no_error: false,
+        }
+    }
+    /// Get the kind of the api driving contexts of this config.
+    pub fn api_kind(&self) -> ApiKind {
+        match self.profile {
+            ContextProfile::Core | ContextProfile::Compatibility => ApiKind::Gl,
+            ContextProfile::Es => ApiKind::Gles,
+        }
+    }
+    /// Whether the profile is passed to the backend. Desktop opengl before 3.2 has no profiles.
+    pub fn has_profile(&self) -> bool {
+        self.profile != ContextProfile::Es && self.version >= Version::new(3, 2)
+    }
+}
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self::of_bindings()
+    }
+}
@@ -1,28 +1,66 @@
-use std::os::raw::c_void;
+use std::os::raw::{c_char, c_void};
+use std::ffi::CStr;
 use std::sync::Arc;
 use std::rc::{Rc, Weak};
-use std::cell::UnsafeCell;
+use std::cell::{RefCell, UnsafeCell};
+use std::collections::HashMap;
+use std::mem;
 
 use Error;
 use gl;
 use gl::Gl;
+use gl::types::{GLenum, GLint, GLuint};
+use GlError;
+use ResetStatus;
+use get_graphics_reset_status;
 use Api;
+use ExternalChanges;
+use ApiInfo;
 use Context;
+use SharingGroup;
+use SharingGroupId;
 use StateCache;
 
 
-/// Whether an api exists.
-/// This is used to prevent two or more apis to exist at the same time.
 thread_local! {
+    /// The current context of this thread, which is shared by the objects created while it is
+    /// current, and switched to another context by make_current.
     static CURRENT_CONTEXT_: UnsafeCell<Weak<CurrentContext>> = UnsafeCell::new(Weak::new());
 }
 
+thread_local! {
+    /// The hidden contexts created by make_sharing_group_current in this thread.
+    static HIDDEN_CONTEXTS_: RefCell<HashMap<SharingGroupId, Rc<Context>>> = RefCell::new(HashMap::new());
+}
+
+/// Maximum number of errors drained by glGetError at once.
+/// The error flag of a lost context may never be cleared, so draining has to stop somewhere.
+const MAX_DRAINED_ERRORS_: usize = 16;
+
+/// Binding of vertex arrays, which is not part of every generated binding.
+#[cfg(any(feature = "gl33", feature = "gl45", feature = "gles3"))]
+const VERTEX_ARRAY_BINDING_: GLenum = 0x85B5;
+
 pub struct CurrentContext {
     context_: UnsafeCell<Rc<Context>>,
     api_: Arc<Api>,
     state_cache_: StateCache,
+    released_: bool,
 }
 impl CurrentContext {
+    /// Release the current context and clear the current context of the thread.
+    /// Unlike dropping the last reference, an error of the backend is returned instead of being
+    /// passed to the error hook of the api.
+    /// Returns Error::ContextInUse if it is still referenced, for example by objects.
+    pub fn release(this: Rc<Self>) -> Result<(), Error> {
+        match Rc::try_unwrap(this) {
+            Ok(mut current_context) => {
+                current_context.released_ = true;
+                unsafe { current_context.api().backend().clear_current_context() }
+            },
+            Err(_) => Err(Error::ContextInUse),
+        }
+    }
     pub fn state_cache(&self) -> &StateCache {
         &self.state_cache_
     }
@@ -35,11 +73,168 @@ impl CurrentContext {
     pub fn gl(&self) -> &Gl {
         self.api().gl()
     }
+    /// Get the sharing group of the current context.
+    pub fn sharing_group(&self) -> &Arc<SharingGroup> {
+        self.context().sharing_group()
+    }
+    /// Returns Error::WrongSharingGroup if the current context is not part of the sharing group
+    /// `sharing_group_id`, and Error::ContextLost if the sharing group was lost.
+    pub fn check_sharing_group(&self, sharing_group_id: SharingGroupId) -> Result<(), Error> {
+        if self.sharing_group().id() != sharing_group_id {
+            Err(Error::WrongSharingGroup)
+        } else if self.sharing_group().is_lost() {
+            Err(Error::ContextLost)
+        } else {
+            Ok(())
+        }
+    }
+    /// Returns Error::ContextLost if the sharing group was lost. Objects are not created in a lost
+    /// sharing group, because they could never be deleted.
+    pub(crate) fn check_not_lost(&self) -> Result<(), Error> {
+        if self.sharing_group().is_lost() {
+            Err(Error::ContextLost)
+        } else {
+            Ok(())
+        }
+    }
+    /// Query whether the context was reset using glGetGraphicsResetStatus of opengl 4.5,
+    /// KHR_robustness, ARB_robustness or EXT_robustness. The context has to be created robust by
+    /// the backend to be notified about resets.
+    /// If it was reset, the sharing group is marked as lost and the reset callback of the api is
+    /// called. The objects of the sharing group return Error::ContextLost afterwards.
+    /// Returns Error::Unsupported if no variant of glGetGraphicsResetStatus is loaded.
+    pub fn reset_status(&self) -> Result<ResetStatus, Error> {
+        let reset_status = match unsafe { get_graphics_reset_status(self.gl()) } {
+            Some(enum_val) => ResetStatus::from_enum_val(enum_val),
+            None => return Err(Error::Unsupported("glGetGraphicsResetStatus")),
+        };
+        if reset_status != ResetStatus::NoError {
+            self.mark_lost_(reset_status);
+        }
+        Ok(reset_status)
+    }
+    /// Mark the sharing group as lost and call the reset callback, unless it was lost before.
+    fn mark_lost_(&self, reset_status: ResetStatus) {
+        if self.sharing_group().mark_lost() {
+            self.api().notify_reset(self.sharing_group(), reset_status);
+        }
+    }
+    /// Whether the extension `name` is supported by the current context.
+    /// Uses the extensions of the api info once it is queried.
+    pub fn has_extension(&self, name: &str) -> bool {
+        if let Some(info) = self.api().info() {
+            return info.has_extension(name);
+        }
+        unsafe {
+            let extensions = self.gl().GetString(gl::EXTENSIONS);
+            if extensions.is_null() {
+                let _ = self.drain_errors("glGetString");
+                return false;
+            }
+            CStr::from_ptr(extensions as *const c_char).to_bytes()
+                .split(|byte| *byte == b' ')
+                .any(|extension| extension == name.as_bytes())
+        }
+    }
+    /// Returns Error::MissingFunctions naming the procedures of `functions` which were not
+    /// loaded.
+    pub fn require_functions(&self, functions: &[(&'static str, &gl::FnPtr)]) -> Result<(), Error> {
+        let missing: Vec<String> = functions.iter()
+            .filter(|&&(_, function)| !function.is_loaded())
+            .map(|&(name, _)| name.to_string())
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::MissingFunctions(missing))
+        }
+    }
+    /// Check the errors of the opengl call `call`, if the error checking policy of the api
+    /// says so. Returns Error::Gl naming `call` if any error flag was set.
+    pub fn check_errors(&self, call: &'static str) -> Result<(), Error> {
+        if self.api().error_check().checks_calls() {
+            self.drain_errors(call)
+        } else {
+            Ok(())
+        }
+    }
+    /// Check the errors raised since the last check, if the error checking policy of the api
+    /// says so. Should be called once per frame.
+    pub fn check_frame_errors(&self) -> Result<(), Error> {
+        if self.api().error_check().checks_frames() {
+            self.drain_errors("frame")
+        } else {
+            Ok(())
+        }
+    }
+    /// Rebuild the state cache by querying the bound objects using glGetIntegerv.
+    /// Bindings which can not be queried are cleared.
+    pub fn resync_state_cache(&self) {
+        let state_cache = self.state_cache();
+        state_cache.bound_array_buffer_gl_handle.set(self.get_binding_(gl::ARRAY_BUFFER_BINDING));
+        state_cache.bound_element_array_buffer_gl_handle.set(self.get_binding_(gl::ELEMENT_ARRAY_BUFFER_BINDING));
+        #[cfg(any(feature = "gl33", feature = "gl45", feature = "gles3"))]
+        state_cache.bound_vertex_array_gl_handle.set(self.get_binding_(VERTEX_ARRAY_BINDING_));
+    }
+    /// Query the handle of a bound object. Returns 0 if the query raised an error.
+    fn get_binding_(&self, pname: GLenum) -> GLuint {
+        let mut val: GLint = 0;
+        unsafe {
+            self.gl().GetIntegerv(pname, &mut val as *mut GLint);
+        }
+        match self.drain_errors("glGetIntegerv") {
+            Ok(()) => val as GLuint,
+            Err(_) => 0,
+        }
+    }
+    /// Whether the context is current according to the strategy against external changes.
+    fn is_context_current_(&self) -> bool {
+        match self.api().external_changes() {
+            ExternalChanges::Trust => true,
+            ExternalChanges::CheckCurrent | ExternalChanges::Resync => self.context().backend().is_current(),
+        }
+    }
+    /// Drain the error flags using glGetError, regardless of the error checking policy.
+    pub(crate) fn drain_errors(&self, call: &'static str) -> Result<(), Error> {
+        let mut errors = Vec::new();
+        unsafe {
+            while let Some(error) = GlError::from_enum_val(self.gl().GetError()) {
+                errors.push(error);
+                if error == GlError::ContextLost || errors.len() >= MAX_DRAINED_ERRORS_ {
+                    break;
+                }
+            }
+        }
+        if errors.contains(&GlError::ContextLost) {
+            let reset_status = unsafe { get_graphics_reset_status(self.gl()) }.map(ResetStatus::from_enum_val);
+            match reset_status {
+                Some(ResetStatus::NoError) | None => self.mark_lost_(ResetStatus::UnknownContextReset),
+                Some(reset_status) => self.mark_lost_(reset_status),
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Gl { call: call, errors: errors })
+        }
+    }
 }
 
+/// Make the context current in this thread.
+/// If the context is already the current one, the strategy of the api against external changes
+/// decides whether the backend is asked and whether the state cache is kept, see ExternalChanges.
+/// Returns Error::ApiMismatch if a current context of another api is still in use in this thread.
 pub fn make_current(context: Rc<Context>) -> Result<Rc<CurrentContext>, Error> {
     unsafe {
         CURRENT_CONTEXT_.with(|thread_local_current_context| {
+            if let Some(current_context) = (*thread_local_current_context.get()).upgrade() {
+                if Rc::ptr_eq(current_context.context(), &context) && current_context.is_context_current_() {
+                    if current_context.api().external_changes() == ExternalChanges::Resync {
+                        current_context.resync_state_cache();
+                    }
+                    return Ok(current_context);
+                }
+            }
             let current_context = match (*thread_local_current_context.get()).upgrade() {
                 None => {
                     let api = context.api().clone();
@@ -47,27 +242,132 @@ pub fn make_current(context: Rc<Context>) -> Result<Rc<CurrentContext>, Error> {
                         context_: UnsafeCell::new(context),
                         api_: api,
                         state_cache_: StateCache::new(),
+                        released_: false,
                     });
                     *thread_local_current_context.get() = Rc::downgrade(&current_context);
                     current_context
                 },
                 Some(current_context) => {
+                    // The objects using the current context use its api, so it can not change.
+                    if current_context.api().id() != context.api().id() {
+                        return Err(Error::ApiMismatch);
+                    }
                     *current_context.context_.get() = context;
                     current_context.state_cache_.clear();
                     current_context
                 },
             };
             if !current_context.context().backend().is_current() {
-                try!(current_context.context().backend().make_current());
+                try!(current_context.context().backend().make_current().map_err(|error| Error::ContextSwitch(Box::new(error))));
+            }
+            if current_context.api().info().is_none() {
+                current_context.api().init_info(ApiInfo::query(&current_context));
+            }
+            if current_context.api().external_changes() == ExternalChanges::Resync {
+                current_context.resync_state_cache();
             }
             Ok(current_context)
         })
     }
 }
+/// Make a context of the sharing group current in this thread.
+/// Reuses the current context if it belongs to the sharing group, which is made current again
+/// like by make_current. Otherwise a hidden context of the sharing group is made current.
+/// The hidden context is created once per thread and sharing group. It keeps the sharing group
+/// alive until release_hidden_contexts is called in the thread or the thread exits, unless it
+/// is the last user of the sharing group, which is checked by the next call.
+/// Returns Error::Unsupported for the sharing group of a single context, which is not current.
+pub fn make_sharing_group_current(sharing_group: &Arc<SharingGroup>) -> Result<Rc<CurrentContext>, Error> {
+    let current_context = CURRENT_CONTEXT_.with(|thread_local_current_context| unsafe {
+        (*thread_local_current_context.get()).upgrade()
+    });
+    if let Some(current_context) = current_context {
+        if current_context.sharing_group().id() == sharing_group.id() {
+            return make_current(current_context.context().clone());
+        }
+    }
+    // The unused contexts are dropped after the map is borrowed, because dropping may make
+    // them current.
+    let (hidden_context, unused_contexts) = HIDDEN_CONTEXTS_.with(|hidden_contexts| {
+        let mut hidden_contexts = hidden_contexts.borrow_mut();
+        let unused: Vec<SharingGroupId> = hidden_contexts.iter()
+            .filter(|&(_, context)| Rc::strong_count(context) == 1 && Arc::strong_count(context.sharing_group()) == 1)
+            .map(|(&sharing_group_id, _)| sharing_group_id)
+            .collect();
+        let unused_contexts: Vec<Rc<Context>> = unused.iter().filter_map(|sharing_group_id| hidden_contexts.remove(sharing_group_id)).collect();
+        (hidden_contexts.get(&sharing_group.id()).cloned(), unused_contexts)
+    });
+    drop(unused_contexts);
+    let hidden_context = match hidden_context {
+        Some(hidden_context) => hidden_context,
+        None => {
+            let hidden_context = try!(SharingGroup::create_context(sharing_group));
+            HIDDEN_CONTEXTS_.with(|hidden_contexts| hidden_contexts.borrow_mut().insert(sharing_group.id(), hidden_context.clone()));
+            hidden_context
+        },
+    };
+    make_current(hidden_context)
+}
+/// Drop the hidden contexts created by make_sharing_group_current in this thread, so they do not
+/// keep their sharing groups alive. A hidden context which is current stays alive as long as the
+/// current context is referenced.
+pub fn release_hidden_contexts() {
+    let hidden_contexts = HIDDEN_CONTEXTS_.with(|hidden_contexts| mem::replace(&mut *hidden_contexts.borrow_mut(), HashMap::new()));
+    drop(hidden_contexts);
+}
+/// Make the context current, call `f` and make the previously current context current again.
+/// The state cache of the previous context is saved and restored, because switching the context
+/// does not change its bindings. The previous context is restored even if `f` panics. An error
+/// restoring it is passed to the error hook of the api.
+/// Returns the result of `f`, or the error of switching to the context. If no context was current
+/// before, the context stays current as long as the current context is referenced.
+pub fn with_context<R, F: FnOnce(&Rc<CurrentContext>) -> R>(context: &Rc<Context>, f: F) -> Result<R, Error> {
+    let previous = CURRENT_CONTEXT_.with(|thread_local_current_context| unsafe {
+        (*thread_local_current_context.get()).upgrade().map(|previous_context| {
+            (previous_context.context().clone(), previous_context.state_cache().clone())
+        })
+    });
+    let current_context = try!(make_current(context.clone()));
+    let _restore_guard = RestoreGuard_ {
+        previous_: previous.filter(|&(ref previous_context, _)| !Rc::ptr_eq(previous_context, context)),
+    };
+    Ok(f(&current_context))
+}
+/// Makes the previous context of with_context current again on drop.
+struct RestoreGuard_ {
+    previous_: Option<(Rc<Context>, StateCache)>,
+}
+impl Drop for RestoreGuard_ {
+    fn drop(&mut self) {
+        if let Some((previous_context, previous_state_cache)) = self.previous_.take() {
+            let api = previous_context.api().clone();
+            match make_current(previous_context) {
+                Ok(current_context) => current_context.state_cache().copy_from(&previous_state_cache),
+                Err(error) => api.report_drop_error("restoring the previous context", error),
+            }
+        }
+    }
+}
+/// Make the context of the current context of this thread current again in its backend, or
+/// clear the current context using `api` if there is none. Used after a context was made current
+/// bypassing the current context.
+pub(crate) unsafe fn restore_current_context(api: &Api) -> Result<(), Error> {
+    // The thread local may be destroyed already, if hidden contexts are dropped at thread exit.
+    let current_context = CURRENT_CONTEXT_.try_with(|thread_local_current_context| {
+        (*thread_local_current_context.get()).upgrade()
+    }).unwrap_or(None);
+    match current_context {
+        Some(current_context) => current_context.context().backend().make_current(),
+        None => api.backend().clear_current_context(),
+    }
+}
 impl Drop for CurrentContext {
     fn drop(&mut self) {
-        unsafe {
-            self.api().backend().clear_current_context().unwrap();
+        if self.released_ {
+            return;
+        }
+        if let Err(error) = unsafe { self.api().backend().clear_current_context() } {
+            self.api().report_drop_error("clearing the current context", error);
         }
     }
 }
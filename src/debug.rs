@@ -0,0 +1,329 @@
+use std::os::raw::{c_char, c_void};
+use std::ffi::CStr;
+use std::slice;
+use std::ptr;
+use std::rc::Rc;
+use std::panic::{self, AssertUnwindSafe};
+
+use gl;
+use gl::Gl;
+use gl::types::{GLenum, GLuint, GLsizei, GLchar};
+
+use Error;
+use ApiKind;
+
+use Context;
+use CurrentContext;
+
+/// Source of a debug message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugSource {
+    Api,
+    WindowSystem,
+    ShaderCompiler,
+    ThirdParty,
+    Application,
+    Other,
+}
+impl DebugSource {
+    /// Get the source from its enumeration value.
+    pub fn from_enum_val(enum_val: GLenum) -> Self {
+        match enum_val {
+            gl::DEBUG_SOURCE_API => DebugSource::Api,
+            gl::DEBUG_SOURCE_WINDOW_SYSTEM => DebugSource::WindowSystem,
+            gl::DEBUG_SOURCE_SHADER_COMPILER => DebugSource::ShaderCompiler,
+            gl::DEBUG_SOURCE_THIRD_PARTY => DebugSource::ThirdParty,
+            gl::DEBUG_SOURCE_APPLICATION => DebugSource::Application,
+            _ => DebugSource::Other,
+        }
+    }
+    /// Get the enumeration value.
+    pub fn enum_val(&self) -> GLenum {
+        match *self {
+            DebugSource::Api => gl::DEBUG_SOURCE_API,
+            DebugSource::WindowSystem => gl::DEBUG_SOURCE_WINDOW_SYSTEM,
+            DebugSource::ShaderCompiler => gl::DEBUG_SOURCE_SHADER_COMPILER,
+            DebugSource::ThirdParty => gl::DEBUG_SOURCE_THIRD_PARTY,
+            DebugSource::Application => gl::DEBUG_SOURCE_APPLICATION,
+            DebugSource::Other => gl::DEBUG_SOURCE_OTHER,
+        }
+    }
+}
+
+/// Type of a debug message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugType {
+    Error,
+    DeprecatedBehavior,
+    UndefinedBehavior,
+    Portability,
+    Performance,
+    Marker,
+    PushGroup,
+    PopGroup,
+    Other,
+}
+impl DebugType {
+    /// Get the type from its enumeration value.
+    pub fn from_enum_val(enum_val: GLenum) -> Self {
+        match enum_val {
+            gl::DEBUG_TYPE_ERROR => DebugType::Error,
+            gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => DebugType::DeprecatedBehavior,
+            gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => DebugType::UndefinedBehavior,
+            gl::DEBUG_TYPE_PORTABILITY => DebugType::Portability,
+            gl::DEBUG_TYPE_PERFORMANCE => DebugType::Performance,
+            gl::DEBUG_TYPE_MARKER => DebugType::Marker,
+            gl::DEBUG_TYPE_PUSH_GROUP => DebugType::PushGroup,
+            gl::DEBUG_TYPE_POP_GROUP => DebugType::PopGroup,
+            _ => DebugType::Other,
+        }
+    }
+    /// Get the enumeration value.
+    pub fn enum_val(&self) -> GLenum {
+        match *self {
+            DebugType::Error => gl::DEBUG_TYPE_ERROR,
+            DebugType::DeprecatedBehavior => gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR,
+            DebugType::UndefinedBehavior => gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR,
+            DebugType::Portability => gl::DEBUG_TYPE_PORTABILITY,
+            DebugType::Performance => gl::DEBUG_TYPE_PERFORMANCE,
+            DebugType::Marker => gl::DEBUG_TYPE_MARKER,
+            DebugType::PushGroup => gl::DEBUG_TYPE_PUSH_GROUP,
+            DebugType::PopGroup => gl::DEBUG_TYPE_POP_GROUP,
+            DebugType::Other => gl::DEBUG_TYPE_OTHER,
+        }
+    }
+}
+
+/// Severity of a debug message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugSeverity {
+    High,
+    Medium,
+    Low,
+    Notification,
+}
+impl DebugSeverity {
+    /// Get the severity from its enumeration value.
+    pub fn from_enum_val(enum_val: GLenum) -> Self {
+        match enum_val {
+            gl::DEBUG_SEVERITY_HIGH => DebugSeverity::High,
+            gl::DEBUG_SEVERITY_MEDIUM => DebugSeverity::Medium,
+            gl::DEBUG_SEVERITY_LOW => DebugSeverity::Low,
+            _ => DebugSeverity::Notification,
+        }
+    }
+    /// Get the enumeration value.
+    pub fn enum_val(&self) -> GLenum {
+        match *self {
+            DebugSeverity::High => gl::DEBUG_SEVERITY_HIGH,
+            DebugSeverity::Medium => gl::DEBUG_SEVERITY_MEDIUM,
+            DebugSeverity::Low => gl::DEBUG_SEVERITY_LOW,
+            DebugSeverity::Notification => gl::DEBUG_SEVERITY_NOTIFICATION,
+        }
+    }
+}
+
+/// A message of the debug output.
+#[derive(Clone, Copy, Debug)]
+pub struct DebugMessage<'a> {
+    pub source: DebugSource,
+    pub type_: DebugType,
+    pub id: GLuint,
+    pub severity: DebugSeverity,
+    pub message: &'a str,
+}
+
+/// Callback receiving the messages of the debug output of a context.
+pub type DebugCallback = Fn(&DebugMessage) + 'static;
+
+/// Debug callback which forwards the messages to the log crate.
+#[cfg(feature = "log")]
+pub fn log_debug_message(message: &DebugMessage) {
+    match message.severity {
+        DebugSeverity::High => error!("gl {:?} {:?} {}: {}", message.source, message.type_, message.id, message.message),
+        DebugSeverity::Medium => warn!("gl {:?} {:?} {}: {}", message.source, message.type_, message.id, message.message),
+        DebugSeverity::Low => info!("gl {:?} {:?} {}: {}", message.source, message.type_, message.id, message.message),
+        DebugSeverity::Notification => debug!("gl {:?} {:?} {}: {}", message.source, message.type_, message.id, message.message),
+    }
+}
+
+/// The function installed using glDebugMessageCallback.
+/// The user param points to the boxed callback owned by the context, or is null after the
+/// callback was removed. A panic of the callback is caught, because it must not unwind into
+/// opengl. It is reported by the panic hook and the message is dropped.
+extern "system" fn debug_message_callback_(source: GLenum, type_: GLenum, id: GLuint, severity: GLenum, length: GLsizei, message: *const GLchar, user_param: *mut c_void) {
+    if user_param.is_null() {
+        return;
+    }
+    unsafe {
+        let callback = &*(user_param as *const Box<DebugCallback>);
+        let bytes = if length < 0 {
+            CStr::from_ptr(message as *const c_char).to_bytes()
+        } else {
+            slice::from_raw_parts(message as *const u8, length as usize)
+        };
+        let message = String::from_utf8_lossy(bytes);
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+            callback(&DebugMessage {
+                source: DebugSource::from_enum_val(source),
+                type_: DebugType::from_enum_val(type_),
+                id: id,
+                severity: DebugSeverity::from_enum_val(severity),
+                message: &message,
+            })
+        }));
+    }
+}
+
+/// Reset the user param of the installed function to null, so the boxed callback can be
+/// dropped. The context of the callback has to be current.
+pub(crate) unsafe fn reset_debug_message_callback(gl: &Gl) {
+    if gl.DebugMessageCallback.is_loaded() {
+        gl.DebugMessageCallback(debug_message_callback_, ptr::null());
+    }
+}
+
+/// Guard of a debug group pushed using glPushDebugGroup. The group is popped when the guard is
+/// dropped. The group is not popped, if another context was made current in the meantime.
+pub struct DebugGroup<'cc> {
+    current_context_: &'cc CurrentContext,
+    /// The context the group was pushed to. None if KHR_debug is not supported.
+    context_: Option<Rc<Context>>,
+}
+impl<'cc> Drop for DebugGroup<'cc> {
+    fn drop(&mut self) {
+        if let Some(ref context) = self.context_ {
+            if &**context as *const Context == &**self.current_context_.context() as *const Context {
+                unsafe {
+                    self.current_context_.gl().PopDebugGroup();
+                }
+                // Errors can not be propagated out of drop, so they are passed to the error hook.
+                if let Err(error) = self.current_context_.check_errors("glPopDebugGroup") {
+                    self.current_context_.api().report_drop_error("glPopDebugGroup", error);
+                }
+            }
+        }
+    }
+}
+
+/// Which extension provides the debug output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DebugOutputExtension_ {
+    KhrDebug,
+    ArbDebugOutput,
+}
+
+impl CurrentContext {
+    fn debug_output_extension_(&self) -> Option<DebugOutputExtension_> {
+        if !self.gl().DebugMessageCallback.is_loaded() || !self.gl().DebugMessageControl.is_loaded() {
+            None
+        } else if self.has_extension("GL_KHR_debug") {
+            Some(DebugOutputExtension_::KhrDebug)
+        } else if self.api().kind() == ApiKind::Gl && self.has_extension("GL_ARB_debug_output") {
+            Some(DebugOutputExtension_::ArbDebugOutput)
+        } else {
+            None
+        }
+    }
+    /// Label the object `gl_handle` of type `identifier` using glObjectLabel.
+    /// Does nothing if KHR_debug is not supported.
+    /// The objects keep their label themselves, instead of reading it back using
+    /// glGetObjectLabel. So it is available without KHR_debug, and errors can name the object
+    /// without another opengl call. Labels set by raw opengl calls are not seen.
+    pub(crate) fn object_label(&self, identifier: GLenum, gl_handle: GLuint, label: &str) -> Result<(), Error> {
+        if !self.gl().ObjectLabel.is_loaded() || !self.has_extension("GL_KHR_debug") {
+            return Ok(());
+        }
+        unsafe {
+            self.gl().ObjectLabel(identifier, gl_handle, label.len() as GLsizei, label.as_ptr() as *const GLchar);
+            try!(self.check_errors("glObjectLabel"));
+            Ok(())
+        }
+    }
+    /// Push a debug group with the message `message`, which is popped when the returned guard
+    /// is dropped. Does nothing if KHR_debug is not supported.
+    pub fn debug_group(&self, message: &str) -> Result<DebugGroup, Error> {
+        if !self.gl().PushDebugGroup.is_loaded() || !self.has_extension("GL_KHR_debug") {
+            return Ok(DebugGroup {
+                current_context_: self,
+                context_: None,
+            });
+        }
+        unsafe {
+            self.gl().PushDebugGroup(gl::DEBUG_SOURCE_APPLICATION, 0, message.len() as GLsizei, message.as_ptr() as *const GLchar);
+            try!(self.check_errors("glPushDebugGroup"));
+        }
+        Ok(DebugGroup {
+            current_context_: self,
+            context_: Some(self.context().clone()),
+        })
+    }
+    /// Whether the debug output is available, either using KHR_debug or ARB_debug_output.
+    pub fn has_debug_output(&self) -> bool {
+        self.debug_output_extension_().is_some()
+    }
+    /// Install a callback receiving the messages of the debug output of the context.
+    /// The messages are reported synchronously, so the callback is called on the thread of the
+    /// opengl call which caused the message. It replaces a previously installed callback.
+    pub fn set_debug_callback<F: Fn(&DebugMessage) + 'static>(&self, callback: F) -> Result<(), Error> {
+        let extension = match self.debug_output_extension_() {
+            Some(extension) => extension,
+            None => return Err(Error::Unsupported("GL_KHR_debug")),
+        };
+        let callback: Box<Box<DebugCallback>> = Box::new(Box::new(callback));
+        unsafe {
+            let gl = self.gl();
+            if extension == DebugOutputExtension_::KhrDebug {
+                gl.Enable(gl::DEBUG_OUTPUT);
+                try!(self.check_errors("glEnable"));
+            }
+            gl.Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+            try!(self.check_errors("glEnable"));
+            gl.DebugMessageCallback(debug_message_callback_, &*callback as *const Box<DebugCallback> as *const c_void);
+            try!(self.check_errors("glDebugMessageCallback"));
+        }
+        // The previous callback is not dropped before the new one is installed.
+        *self.context().debug_callback().borrow_mut() = Some(callback);
+        Ok(())
+    }
+    /// Remove the callback receiving the messages of the debug output.
+    pub fn clear_debug_callback(&self) -> Result<(), Error> {
+        if self.context().debug_callback().borrow().is_none() {
+            return Ok(());
+        }
+        unsafe {
+            let gl = self.gl();
+            if self.debug_output_extension_() == Some(DebugOutputExtension_::KhrDebug) {
+                gl.Disable(gl::DEBUG_OUTPUT);
+                try!(self.check_errors("glDisable"));
+            }
+            gl.Disable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+            try!(self.check_errors("glDisable"));
+            reset_debug_message_callback(gl);
+            try!(self.check_errors("glDebugMessageCallback"));
+        }
+        *self.context().debug_callback().borrow_mut() = None;
+        Ok(())
+    }
+    /// Enable or disable debug messages using glDebugMessageControl.
+    /// None stands for any source, type or severity.
+    /// If `ids` is not empty, only the messages with these ids are affected. In this case the
+    /// source and type have to be specified and the severity must not.
+    pub fn debug_message_control(&self, source: Option<DebugSource>, type_: Option<DebugType>, severity: Option<DebugSeverity>, ids: &[GLuint], enabled: bool) -> Result<(), Error> {
+        if !self.has_debug_output() {
+            return Err(Error::Unsupported("GL_KHR_debug"));
+        }
+        unsafe {
+            self.gl().DebugMessageControl(
+                source.map_or(gl::DONT_CARE, |source| source.enum_val()),
+                type_.map_or(gl::DONT_CARE, |type_| type_.enum_val()),
+                severity.map_or(gl::DONT_CARE, |severity| severity.enum_val()),
+                ids.len() as GLsizei,
+                ids.as_ptr(),
+                if enabled { gl::TRUE } else { gl::FALSE },
+            );
+            try!(self.check_errors("glDebugMessageControl"));
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use std::os::raw::{c_char, c_void};
+use std::ffi::{CStr, CString};
+use std::sync::Arc;
+use std::error;
+use std::fmt;
+use std::mem::transmute;
+use std::ptr;
+
+use Error;
+use ApiBackend;
+use MakeApiBackend;
+use ContextBackend;
+use SharingGroupBackend;
+use ContextConfig;
+use ContextProfile;
+
+#[allow(non_camel_case_types)]
+type EGLDisplay = *mut c_void;
+#[allow(non_camel_case_types)]
+type EGLConfig = *mut c_void;
+#[allow(non_camel_case_types)]
+type EGLContext = *mut c_void;
+#[allow(non_camel_case_types)]
+type EGLSurface = *mut c_void;
+#[allow(non_camel_case_types)]
+type EGLint = i32;
+#[allow(non_camel_case_types)]
+type EGLBoolean = u32;
+#[allow(non_camel_case_types)]
+type EGLenum = u32;
+
+const EGL_FALSE: EGLBoolean = 0;
+const EGL_SUCCESS: EGLint = 0x3000;
+const EGL_BAD_CONFIG: EGLint = 0x3005;
+const EGL_NONE: EGLint = 0x3038;
+const EGL_EXTENSIONS: EGLint = 0x3055;
+const EGL_SURFACE_TYPE: EGLint = 0x3033;
+const EGL_PBUFFER_BIT: EGLint = 0x0001;
+const EGL_RENDERABLE_TYPE: EGLint = 0x3040;
+const EGL_OPENGL_BIT: EGLint = 0x0008;
+const EGL_OPENGL_ES2_BIT: EGLint = 0x0004;
+const EGL_OPENGL_ES3_BIT_KHR: EGLint = 0x0040;
+const EGL_DEPTH_SIZE: EGLint = 0x3025;
+const EGL_STENCIL_SIZE: EGLint = 0x3026;
+const EGL_SAMPLES: EGLint = 0x3031;
+const EGL_SAMPLE_BUFFERS: EGLint = 0x3032;
+const EGL_RED_SIZE: EGLint = 0x3024;
+const EGL_GREEN_SIZE: EGLint = 0x3023;
+const EGL_BLUE_SIZE: EGLint = 0x3022;
+const EGL_WIDTH: EGLint = 0x3057;
+const EGL_HEIGHT: EGLint = 0x3056;
+const EGL_OPENGL_API: EGLenum = 0x30A2;
+const EGL_OPENGL_ES_API: EGLenum = 0x30A0;
+const EGL_PLATFORM_SURFACELESS_MESA: EGLenum = 0x31DD;
+const EGL_CONTEXT_OPENGL_ROBUST_ACCESS_EXT: EGLint = 0x30BF;
+const EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_EXT: EGLint = 0x3138;
+const EGL_LOSE_CONTEXT_ON_RESET_EXT: EGLint = 0x31BF;
+const EGL_CONTEXT_MAJOR_VERSION_KHR: EGLint = 0x3098;
+const EGL_CONTEXT_MINOR_VERSION_KHR: EGLint = 0x30FB;
+const EGL_CONTEXT_FLAGS_KHR: EGLint = 0x30FC;
+const EGL_CONTEXT_OPENGL_PROFILE_MASK_KHR: EGLint = 0x30FD;
+const EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT_KHR: EGLint = 0x0001;
+const EGL_CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT_KHR: EGLint = 0x0002;
+const EGL_CONTEXT_OPENGL_DEBUG_BIT_KHR: EGLint = 0x0001;
+const EGL_CONTEXT_OPENGL_FORWARD_COMPATIBLE_BIT_KHR: EGLint = 0x0002;
+const EGL_CONTEXT_OPENGL_ROBUST_ACCESS_BIT_KHR: EGLint = 0x0004;
+const EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_KHR: EGLint = 0x31BD;
+const EGL_LOSE_CONTEXT_ON_RESET_KHR: EGLint = 0x31BF;
+const EGL_CONTEXT_OPENGL_NO_ERROR_KHR: EGLint = 0x31B3;
+const EGL_GL_COLORSPACE_KHR: EGLint = 0x309D;
+const EGL_GL_COLORSPACE_SRGB_KHR: EGLint = 0x3089;
+
+#[link(name = "EGL")]
+extern "system" {
+    fn eglGetDisplay(display_id: *mut c_void) -> EGLDisplay;
+    fn eglInitialize(dpy: EGLDisplay, major: *mut EGLint, minor: *mut EGLint) -> EGLBoolean;
+    fn eglTerminate(dpy: EGLDisplay) -> EGLBoolean;
+    fn eglQueryString(dpy: EGLDisplay, name: EGLint) -> *const c_char;
+    fn eglBindAPI(api: EGLenum) -> EGLBoolean;
+    fn eglChooseConfig(dpy: EGLDisplay, attrib_list: *const EGLint, configs: *mut EGLConfig, config_size: EGLint, num_config: *mut EGLint) -> EGLBoolean;
+    fn eglCreateContext(dpy: EGLDisplay, config: EGLConfig, share_context: EGLContext, attrib_list: *const EGLint) -> EGLContext;
+    fn eglDestroyContext(dpy: EGLDisplay, ctx: EGLContext) -> EGLBoolean;
+    fn eglCreatePbufferSurface(dpy: EGLDisplay, config: EGLConfig, attrib_list: *const EGLint) -> EGLSurface;
+    fn eglDestroySurface(dpy: EGLDisplay, surface: EGLSurface) -> EGLBoolean;
+    fn eglMakeCurrent(dpy: EGLDisplay, draw: EGLSurface, read: EGLSurface, ctx: EGLContext) -> EGLBoolean;
+    fn eglGetCurrentContext() -> EGLContext;
+    fn eglGetProcAddress(procname: *const c_char) -> *const c_void;
+    fn eglGetError() -> EGLint;
+}
+
+type EglGetPlatformDisplayExt_ = extern "system" fn(platform: EGLenum, native_display: *mut c_void, attrib_list: *const EGLint) -> EGLDisplay;
+
+/// Error of an egl call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EglError {
+    /// The egl call which failed.
+    pub call: &'static str,
+    /// The value returned by eglGetError.
+    pub code: i32,
+}
+impl fmt::Display for EglError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} failed with egl error 0x{:04x}", self.call, self.code)
+    }
+}
+impl error::Error for EglError {
+    fn description(&self) -> &str {
+        "egl error"
+    }
+}
+
+/// Get the error of the failed egl call `call`.
+fn egl_error_(call: &'static str) -> Error {
+    let code = unsafe { eglGetError() };
+    Error::unknown(EglError { call: call, code: if code == EGL_SUCCESS { 0 } else { code } })
+}
+
+/// Whether the space separated extension string `extensions` contains `name`.
+unsafe fn has_egl_extension_(extensions: *const c_char, name: &str) -> bool {
+    !extensions.is_null() && CStr::from_ptr(extensions).to_bytes().split(|byte| *byte == b' ').any(|extension| extension == name.as_bytes())
+}
+
+/// An initialized egl display.
+/// Uses the surfaceless platform of mesa if available, and the default display otherwise.
+pub struct EglDisplay {
+    display_: EGLDisplay,
+    surfaceless_: bool,
+}
+unsafe impl Send for EglDisplay {}
+unsafe impl Sync for EglDisplay {}
+impl EglDisplay {
+    /// Get and initialize the display.
+    pub fn new() -> Result<Arc<Self>, Error> {
+        unsafe {
+            let client_extensions = eglQueryString(ptr::null_mut(), EGL_EXTENSIONS);
+            let mut display = ptr::null_mut();
+            if has_egl_extension_(client_extensions, "EGL_MESA_platform_surfaceless") {
+                let name = CString::new("eglGetPlatformDisplayEXT").unwrap();
+                let get_platform_display = eglGetProcAddress(name.as_ptr());
+                if !get_platform_display.is_null() {
+                    let get_platform_display: EglGetPlatformDisplayExt_ = transmute(get_platform_display);
+                    display = get_platform_display(EGL_PLATFORM_SURFACELESS_MESA, ptr::null_mut(), &EGL_NONE as *const EGLint);
+                }
+            }
+            if display.is_null() {
+                display = eglGetDisplay(ptr::null_mut());
+            }
+            if display.is_null() {
+                return Err(egl_error_("eglGetDisplay"));
+            }
+            let (mut major, mut minor) = (0, 0);
+            if eglInitialize(display, &mut major, &mut minor) == EGL_FALSE {
+                return Err(egl_error_("eglInitialize"));
+            }
+            let surfaceless = has_egl_extension_(eglQueryString(display, EGL_EXTENSIONS), "EGL_KHR_surfaceless_context");
+            Ok(Arc::new(EglDisplay {
+                display_: display,
+                surfaceless_: surfaceless,
+            }))
+        }
+    }
+    /// Whether contexts can be made current without a surface.
+    pub fn surfaceless(&self) -> bool {
+        self.surfaceless_
+    }
+}
+impl Drop for EglDisplay {
+    fn drop(&mut self) {
+        unsafe {
+            eglTerminate(self.display_);
+        }
+    }
+}
+
+/// Creation of an api backend using egl.
+/// One of the contexts of the display has to be current.
+pub struct EglMakeApiBackend {
+    display_: Arc<EglDisplay>,
+    client_api_: EGLenum,
+}
+impl EglMakeApiBackend {
+    /// Create an api backend for the contexts of ContextConfig::of_bindings.
+    pub fn new(display: Arc<EglDisplay>) -> Self {
+        Self::with_config(display, &ContextConfig::of_bindings())
+    }
+    /// Create an api backend for the contexts of the config.
+    pub fn with_config(display: Arc<EglDisplay>, context_config: &ContextConfig) -> Self {
+        EglMakeApiBackend {
+            display_: display,
+            client_api_: client_api_(context_config).0,
+        }
+    }
+}
+unsafe impl MakeApiBackend for EglMakeApiBackend {
+    unsafe fn get_proc_address(&mut self, name: &str) -> *const c_void {
+        match CString::new(name) {
+            Ok(name) => eglGetProcAddress(name.as_ptr()),
+            Err(_) => ptr::null(),
+        }
+    }
+    unsafe fn into_backend(self) -> Box<ApiBackend> {
+        Box::new(EglApiBackend {
+            display_: self.display_,
+            client_api_: self.client_api_,
+        })
+    }
+}
+
+/// Api backend using egl.
+pub struct EglApiBackend {
+    display_: Arc<EglDisplay>,
+    client_api_: EGLenum,
+}
+unsafe impl ApiBackend for EglApiBackend {
+    unsafe fn clear_current_context(&self) -> Result<(), Error> {
+        try!(bind_api_(self.client_api_));
+        if eglMakeCurrent(self.display_.display_, ptr::null_mut(), ptr::null_mut(), ptr::null_mut()) == EGL_FALSE {
+            Err(egl_error_("eglMakeCurrent"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Get the client api and renderable type of the config.
+fn client_api_(context_config: &ContextConfig) -> (EGLenum, EGLint) {
+    match context_config.profile {
+        ContextProfile::Core | ContextProfile::Compatibility => (EGL_OPENGL_API, EGL_OPENGL_BIT),
+        ContextProfile::Es if context_config.version.major >= 3 => (EGL_OPENGL_ES_API, EGL_OPENGL_ES3_BIT_KHR),
+        ContextProfile::Es => (EGL_OPENGL_ES_API, EGL_OPENGL_ES2_BIT),
+    }
+}
+
+/// Bind the client api in this thread. Egl tracks the current context per client api, so it
+/// has to be bound before making a context current, releasing it or querying it.
+unsafe fn bind_api_(client_api: EGLenum) -> Result<(), Error> {
+    if eglBindAPI(client_api) == EGL_FALSE {
+        Err(egl_error_("eglBindAPI"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Get the attributes of eglCreateContext for the config.
+/// Attributes with default values are left out, so only the extensions of the requested features
+/// are required.
+fn context_attribs_(context_config: &ContextConfig) -> Vec<EGLint> {
+    let mut context_attribs = vec![EGL_CONTEXT_MAJOR_VERSION_KHR, context_config.version.major as EGLint];
+    if context_config.version.minor != 0 {
+        context_attribs.extend_from_slice(&[EGL_CONTEXT_MINOR_VERSION_KHR, context_config.version.minor as EGLint]);
+    }
+    if context_config.has_profile() {
+        let profile_mask = match context_config.profile {
+            ContextProfile::Compatibility => EGL_CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT_KHR,
+            _ => EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT_KHR,
+        };
+        context_attribs.extend_from_slice(&[EGL_CONTEXT_OPENGL_PROFILE_MASK_KHR, profile_mask]);
+    }
+    let is_es = context_config.profile == ContextProfile::Es;
+    let mut flags = 0;
+    if context_config.debug {
+        flags |= EGL_CONTEXT_OPENGL_DEBUG_BIT_KHR;
+    }
+    if context_config.forward_compatible && !is_es {
+        flags |= EGL_CONTEXT_OPENGL_FORWARD_COMPATIBLE_BIT_KHR;
+    }
+    // EGL_EXT_create_context_robustness only applies to opengl es, desktop opengl uses the
+    // attributes of EGL_KHR_create_context.
+    if context_config.robust && !is_es {
+        flags |= EGL_CONTEXT_OPENGL_ROBUST_ACCESS_BIT_KHR;
+        context_attribs.extend_from_slice(&[EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_KHR, EGL_LOSE_CONTEXT_ON_RESET_KHR]);
+    }
+    if flags != 0 {
+        context_attribs.extend_from_slice(&[EGL_CONTEXT_FLAGS_KHR, flags]);
+    }
+    if context_config.robust && is_es {
+        context_attribs.extend_from_slice(&[
+            EGL_CONTEXT_OPENGL_ROBUST_ACCESS_EXT, 1,
+            EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_EXT, EGL_LOSE_CONTEXT_ON_RESET_EXT,
+        ]);
+    }
+    if context_config.no_error {
+        context_attribs.extend_from_slice(&[EGL_CONTEXT_OPENGL_NO_ERROR_KHR, 1]);
+    }
+    context_attribs.push(EGL_NONE);
+    context_attribs
+}
+
+/// Offscreen context backend using egl.
+/// Renders without a surface if the display supports it, and to a 1x1 pbuffer otherwise.
+pub struct EglContextBackend {
+    display_: Arc<EglDisplay>,
+    client_api_: EGLenum,
+    context_: EGLContext,
+    surface_: EGLSurface,
+}
+impl EglContextBackend {
+    /// Create a context of ContextConfig::of_bindings, which shares its objects with `share` if
+    /// given.
+    pub fn new(display: Arc<EglDisplay>, share: Option<&EglContextBackend>) -> Result<Self, Error> {
+        Self::with_config(display, share, &ContextConfig::of_bindings())
+    }
+    /// Create a robust context of ContextConfig::of_bindings, which is notified about resets,
+    /// see CurrentContext::reset_status. A shared context has to be robust as well.
+    pub fn new_robust(display: Arc<EglDisplay>, share: Option<&EglContextBackend>) -> Result<Self, Error> {
+        Self::with_config(display, share, &ContextConfig { robust: true, .. ContextConfig::of_bindings() })
+    }
+    /// Create a context of the config, which shares its objects with `share` if given.
+    /// The version, profile and flags require EGL_KHR_create_context, robustness requires
+    /// EGL_KHR_create_context for opengl and EGL_EXT_create_context_robustness for opengl es,
+    /// no_error requires EGL_KHR_create_context_no_error and srgb requires EGL_KHR_gl_colorspace
+    /// for the pbuffer. srgb is ignored if the display is surfaceless.
+    pub fn with_config(display: Arc<EglDisplay>, share: Option<&EglContextBackend>, context_config: &ContextConfig) -> Result<Self, Error> {
+        unsafe {
+            let (client_api, renderable_type) = client_api_(context_config);
+            try!(bind_api_(client_api));
+            let surface_type = if display.surfaceless() { 0 } else { EGL_PBUFFER_BIT };
+            let config_attribs = [
+                EGL_SURFACE_TYPE, surface_type,
+                EGL_RENDERABLE_TYPE, renderable_type,
+                EGL_RED_SIZE, 8,
+                EGL_GREEN_SIZE, 8,
+                EGL_BLUE_SIZE, 8,
+                EGL_DEPTH_SIZE, context_config.depth_bits as EGLint,
+                EGL_STENCIL_SIZE, context_config.stencil_bits as EGLint,
+                EGL_SAMPLE_BUFFERS, if context_config.samples > 0 { 1 } else { 0 },
+                EGL_SAMPLES, context_config.samples as EGLint,
+                EGL_NONE,
+            ];
+            let mut config: EGLConfig = ptr::null_mut();
+            let mut num_config: EGLint = 0;
+            if eglChooseConfig(display.display_, config_attribs.as_ptr(), &mut config, 1, &mut num_config) == EGL_FALSE {
+                return Err(egl_error_("eglChooseConfig"));
+            }
+            if num_config == 0 {
+                return Err(Error::unknown(EglError { call: "eglChooseConfig", code: EGL_BAD_CONFIG }));
+            }
+            let share_context = share.map_or(ptr::null_mut(), |share| share.context_);
+            let context_attribs = context_attribs_(context_config);
+            let context = eglCreateContext(display.display_, config, share_context, context_attribs.as_ptr());
+            if context.is_null() {
+                return Err(egl_error_("eglCreateContext"));
+            }
+            let surface = if display.surfaceless() {
+                ptr::null_mut()
+            } else {
+                let mut surface_attribs = vec![EGL_WIDTH, 1, EGL_HEIGHT, 1];
+                if context_config.srgb {
+                    surface_attribs.extend_from_slice(&[EGL_GL_COLORSPACE_KHR, EGL_GL_COLORSPACE_SRGB_KHR]);
+                }
+                surface_attribs.push(EGL_NONE);
+                let surface = eglCreatePbufferSurface(display.display_, config, surface_attribs.as_ptr());
+                if surface.is_null() {
+                    let error = egl_error_("eglCreatePbufferSurface");
+                    eglDestroyContext(display.display_, context);
+                    return Err(error);
+                }
+                surface
+            };
+            Ok(EglContextBackend {
+                display_: display,
+                client_api_: client_api,
+                context_: context,
+                surface_: surface,
+            })
+        }
+    }
+    /// Get the display.
+    pub fn display(&self) -> &Arc<EglDisplay> {
+        &self.display_
+    }
+}
+unsafe impl ContextBackend for EglContextBackend {
+    fn is_current(&self) -> bool {
+        unsafe { bind_api_(self.client_api_).is_ok() && eglGetCurrentContext() == self.context_ }
+    }
+    unsafe fn make_current(&self) -> Result<(), Error> {
+        try!(bind_api_(self.client_api_));
+        if eglMakeCurrent(self.display_.display_, self.surface_, self.surface_, self.context_) == EGL_FALSE {
+            Err(egl_error_("eglMakeCurrent"))
+        } else {
+            Ok(())
+        }
+    }
+}
+impl Drop for EglContextBackend {
+    fn drop(&mut self) {
+        unsafe {
+            if self.is_current() {
+                eglMakeCurrent(self.display_.display_, ptr::null_mut(), ptr::null_mut(), ptr::null_mut());
+            }
+            if !self.surface_.is_null() {
+                eglDestroySurface(self.display_.display_, self.surface_);
+            }
+            eglDestroyContext(self.display_.display_, self.context_);
+        }
+    }
+}
+
+/// Sharing group backend using egl.
+/// It owns a context, which is never made current, so the objects of the group live on while no
+/// other context of the group exists. The contexts of the group share their objects with it.
+pub struct EglSharingGroupBackend {
+    root_context_: EglContextBackend,
+    context_config_: ContextConfig,
+}
+impl EglSharingGroupBackend {
+    pub fn new(display: Arc<EglDisplay>) -> Result<Self, Error> {
+        Self::with_config(display, ContextConfig::of_bindings())
+    }
+    /// Create a sharing group backend, whose contexts are robust, see
+    /// EglContextBackend::new_robust.
+    pub fn new_robust(display: Arc<EglDisplay>) -> Result<Self, Error> {
+        Self::with_config(display, ContextConfig { robust: true, .. ContextConfig::of_bindings() })
+    }
+    /// Create a sharing group backend, whose contexts are created with the config.
+    pub fn with_config(display: Arc<EglDisplay>, context_config: ContextConfig) -> Result<Self, Error> {
+        Ok(EglSharingGroupBackend {
+            root_context_: try!(EglContextBackend::with_config(display, None, &context_config)),
+            context_config_: context_config,
+        })
+    }
+}
+// The root context is never made current, and egl allows to create contexts sharing with it in
+// every thread.
+unsafe impl Send for EglSharingGroupBackend {}
+unsafe impl Sync for EglSharingGroupBackend {}
+unsafe impl SharingGroupBackend for EglSharingGroupBackend {
+    unsafe fn create_context(&self) -> Result<Box<ContextBackend>, Error> {
+        let context = try!(EglContextBackend::with_config(self.root_context_.display().clone(), Some(&self.root_context_), &self.context_config_));
+        Ok(Box::new(context))
+    }
+}
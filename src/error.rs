@@ -1,13 +1,135 @@
+use gl::types::GLuint;
 use GlError;
+use LiveObject;
+use ShaderCompileError;
 
-use std::any::Any;
-use std::fmt::Debug;
+use std::error;
+use std::fmt;
 
-pub trait UnknownError: Any + Debug + 'static {}
+/// Kind of an object an error occurred with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ObjectKind {
+    Buffer,
+    Shader,
+    Program,
+    VertexArray,
+}
+impl fmt::Display for ObjectKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ObjectKind::Buffer => write!(f, "buffer"),
+            ObjectKind::Shader => write!(f, "shader"),
+            ObjectKind::Program => write!(f, "program"),
+            ObjectKind::VertexArray => write!(f, "vertex array"),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum Error {
-    ApiAlreadyExists,
-    Gl(GlError),
-    Unknown(Box<UnknownError>),
+    /// An object or context was used with a context of another api.
+    ApiMismatch,
+    /// An object was used while a context of another sharing group was current.
+    WrongSharingGroup,
+    /// An object which can not be shared was used while another context was current.
+    WrongContext,
+    /// The opengl call `call` raised the errors `errors`.
+    Gl { call: &'static str, errors: Vec<GlError> },
+    /// The extension or feature is not supported by the context.
+    Unsupported(&'static str),
+    /// The opengl procedures could not be loaded.
+    MissingFunctions(Vec<String>),
+    /// The compilation of a shader failed.
+    ShaderCompile(ShaderCompileError),
+    /// The link of a program failed. Contains the info log.
+    ProgramLink(String),
+    /// The validation of a program failed. Contains the info log.
+    ProgramValidate(String),
+    /// The error `source` occurred with the object of kind `kind`, handle `gl_handle` and
+    /// label `label`.
+    Object { kind: ObjectKind, gl_handle: GLuint, label: Option<String>, source: Box<Error> },
+    /// The error `source` occurred while making a context current.
+    ContextSwitch(Box<Error>),
+    /// The current context could not be released, because it is still referenced.
+    ContextInUse,
+    /// The sharing group of the object was lost by a reset of the context.
+    ContextLost,
+    /// The objects were still alive when their sharing group was dropped.
+    Leaked(Vec<LiveObject>),
+    /// An error not caused by this library, for example by a backend.
+    /// It can be downcast using `downcast_ref` on the boxed error.
+    Unknown(Box<error::Error + Send + Sync>),
+}
+impl Error {
+    /// Create an error not caused by this library.
+    pub fn unknown<E: error::Error + Send + Sync + 'static>(error: E) -> Self {
+        Error::Unknown(Box::new(error))
+    }
+    /// Add the object the error occurred with.
+    pub fn with_object(self, kind: ObjectKind, gl_handle: GLuint, label: Option<&str>) -> Self {
+        Error::Object {
+            kind: kind,
+            gl_handle: gl_handle,
+            label: label.map(|label| label.to_string()),
+            source: Box::new(self),
+        }
+    }
+    /// Get the error without the objects or context switches it occurred with.
+    pub fn root(&self) -> &Error {
+        match *self {
+            Error::Object { ref source, .. } => source.root(),
+            Error::ContextSwitch(ref source) => source.root(),
+            _ => self,
+        }
+    }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::ApiMismatch => write!(f, "used with a context of another api"),
+            Error::WrongSharingGroup => write!(f, "used while a context of another sharing group is current"),
+            Error::WrongContext => write!(f, "used while another context is current"),
+            Error::Gl { call, ref errors } => {
+                try!(write!(f, "{} raised ", call));
+                for (i, error) in errors.iter().enumerate() {
+                    if i != 0 {
+                        try!(write!(f, ", "));
+                    }
+                    try!(write!(f, "{}", error));
+                }
+                Ok(())
+            },
+            Error::Unsupported(name) => write!(f, "{} is not supported", name),
+            Error::MissingFunctions(ref names) => write!(f, "missing opengl functions: {}", names.join(", ")),
+            Error::ShaderCompile(ref error) => write!(f, "{}", error),
+            Error::ProgramLink(ref info_log) => write!(f, "program link failed: {}", info_log.trim()),
+            Error::ProgramValidate(ref info_log) => write!(f, "program validation failed: {}", info_log.trim()),
+            Error::Object { kind, gl_handle, label: Some(ref label), ref source } => write!(f, "{} {} \"{}\": {}", kind, gl_handle, label, source),
+            Error::Object { kind, gl_handle, label: None, ref source } => write!(f, "{} {}: {}", kind, gl_handle, source),
+            Error::ContextSwitch(_) => write!(f, "error making a context current"),
+            Error::ContextInUse => write!(f, "the current context is still referenced"),
+            Error::ContextLost => write!(f, "the context was lost"),
+            Error::Leaked(ref live_objects) => {
+                try!(write!(f, "{} live objects were not deleted:", live_objects.len()));
+                for live_object in live_objects {
+                    try!(write!(f, "\n{}", live_object));
+                }
+                Ok(())
+            },
+            Error::Unknown(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "scgl error"
+    }
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            Error::Object { ref source, .. } => Some(&**source),
+            Error::ContextSwitch(ref source) => Some(&**source),
+            Error::Unknown(ref error) => error.source(),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,702 @@
+use std::os::raw::{c_char, c_void};
+use std::ffi::{CStr, CString};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::cell::RefCell;
+use std::ptr;
+use std::slice;
+
+use gl;
+use gl::types::{GLenum, GLuint, GLint, GLsizei, GLsizeiptr, GLchar, GLubyte, GLboolean, GLbitfield, GLDEBUGPROC};
+
+use Error;
+use ApiBackend;
+use MakeApiBackend;
+use ContextBackend;
+use SharingGroupBackend;
+
+/// A buffer of the fake opengl implementation.
+#[derive(Clone, Debug, Default)]
+pub struct FakeBuffer {
+    pub data: Vec<u8>,
+    pub usage: GLenum,
+    /// The flags of glBufferStorage, if the storage is immutable.
+    pub storage_flags: Option<GLbitfield>,
+}
+
+/// A shader of the fake opengl implementation.
+/// The compilation fails if the source contains an `#error` directive.
+#[derive(Clone, Debug, Default)]
+pub struct FakeShader {
+    pub type_: GLenum,
+    pub source: String,
+    pub compiled: bool,
+    pub info_log: String,
+}
+
+/// A program of the fake opengl implementation.
+/// The link fails unless a compiled vertex and fragment shader are attached.
+#[derive(Clone, Debug, Default)]
+pub struct FakeProgram {
+    pub shaders: Vec<GLuint>,
+    pub linked: bool,
+    pub validated: bool,
+    pub info_log: String,
+}
+
+/// The objects of the fake opengl implementation. They are shared by the contexts of a sharing
+/// group, which may be current in different threads.
+#[derive(Debug, Default)]
+pub struct FakeObjects {
+    next_name_: GLuint,
+    pub buffers: HashMap<GLuint, FakeBuffer>,
+    pub shaders: HashMap<GLuint, FakeShader>,
+    pub programs: HashMap<GLuint, FakeProgram>,
+    /// The labels set by glObjectLabel, by identifier and name.
+    pub labels: HashMap<(GLenum, GLuint), String>,
+}
+impl FakeObjects {
+    fn gen_name_(&mut self) -> GLuint {
+        self.next_name_ += 1;
+        self.next_name_
+    }
+    fn contains_(&self, identifier: GLenum, name: GLuint) -> bool {
+        match identifier {
+            gl::BUFFER => self.buffers.contains_key(&name),
+            gl::SHADER => self.shaders.contains_key(&name),
+            gl::PROGRAM => self.programs.contains_key(&name),
+            _ => false,
+        }
+    }
+}
+
+/// The state of a context of the fake opengl implementation.
+#[derive(Debug)]
+pub struct FakeContextState {
+    /// The objects of the sharing group.
+    pub objects: Arc<Mutex<FakeObjects>>,
+    /// The bound buffers by target.
+    pub bound_buffers: HashMap<GLenum, GLuint>,
+    /// The vertex arrays, which are not shared. Their names start at 1 in every context.
+    pub vertex_arrays: HashSet<GLuint>,
+    /// The bound vertex array.
+    pub bound_vertex_array: GLuint,
+    /// The raised errors, which are not queried by glGetError yet.
+    pub errors: Vec<GLenum>,
+    /// The names of the called opengl procedures.
+    pub calls: Vec<&'static str>,
+    /// The status returned by glGetGraphicsResetStatus, set to simulate a reset.
+    pub reset_status: GLenum,
+    /// The capabilities enabled by glEnable.
+    pub enabled: HashSet<GLenum>,
+    /// The callback and user param installed by glDebugMessageCallback.
+    /// Errors are reported to it, if GL_DEBUG_OUTPUT is enabled.
+    pub debug_callback: Option<(GLDEBUGPROC, *const c_void)>,
+    /// Whether all messages are enabled by glDebugMessageControl.
+    pub debug_messages_enabled: bool,
+    /// The messages of the debug groups pushed by glPushDebugGroup.
+    pub debug_groups: Vec<String>,
+    /// Whether making the context current fails, set to simulate a lost surface.
+    pub make_current_fails: bool,
+    next_vertex_array_name_: GLuint,
+    version_: CString,
+    extensions_: CString,
+}
+impl FakeContextState {
+    fn new(objects: Arc<Mutex<FakeObjects>>) -> Self {
+        FakeContextState {
+            objects: objects,
+            bound_buffers: HashMap::new(),
+            vertex_arrays: HashSet::new(),
+            bound_vertex_array: 0,
+            errors: Vec::new(),
+            calls: Vec::new(),
+            reset_status: gl::NO_ERROR,
+            enabled: HashSet::new(),
+            debug_callback: None,
+            debug_messages_enabled: true,
+            debug_groups: Vec::new(),
+            make_current_fails: false,
+            next_vertex_array_name_: 0,
+            version_: CString::new("2.0 scgl fake").unwrap(),
+            extensions_: CString::new("").unwrap(),
+        }
+    }
+    /// Set the string returned by glGetString(GL_VERSION). Defaults to "2.0 scgl fake".
+    pub fn set_version(&mut self, version: &str) {
+        self.version_ = CString::new(version).unwrap();
+    }
+    /// Set the space separated extensions returned by glGetString(GL_EXTENSIONS).
+    pub fn set_extensions(&mut self, extensions: &str) {
+        self.extensions_ = CString::new(extensions).unwrap();
+    }
+    /// Number of calls of the opengl procedure `name`.
+    pub fn call_count(&self, name: &str) -> usize {
+        self.calls.iter().filter(|call| **call == name).count()
+    }
+    fn raise_(&mut self, error: GLenum) {
+        self.errors.push(error);
+        if let Some((callback, user_param)) = self.debug_callback {
+            if self.enabled.contains(&gl::DEBUG_OUTPUT) && self.debug_messages_enabled {
+                let message = format!("error {:#x} raised", error);
+                callback(gl::DEBUG_SOURCE_API, gl::DEBUG_TYPE_ERROR, error, gl::DEBUG_SEVERITY_HIGH, message.len() as GLsizei, message.as_ptr() as *const GLchar, user_param as *mut c_void);
+            }
+        }
+    }
+}
+
+/// Binding of vertex arrays, which is not part of every generated binding.
+const VERTEX_ARRAY_BINDING_: GLenum = 0x85B5;
+
+thread_local! {
+    /// The fake context current in this thread.
+    static CURRENT_FAKE_CONTEXT_: RefCell<Option<Rc<RefCell<FakeContextState>>>> = RefCell::new(None);
+}
+
+/// Call a function with the state of the fake context current in this thread and record the
+/// call. Panics if no fake context is current, where a real implementation would crash.
+fn with_current_<R, F: FnOnce(&mut FakeContextState) -> R>(call: &'static str, f: F) -> R {
+    CURRENT_FAKE_CONTEXT_.with(|current| {
+        let current = current.borrow();
+        let state = current.as_ref().expect("No fake context current");
+        let mut state = state.borrow_mut();
+        state.calls.push(call);
+        f(&mut *state)
+    })
+}
+
+/// Copy an info log into the buffer of glGet*InfoLog.
+unsafe fn copy_info_log_(info_log: &str, buf_size: GLsizei, length: *mut GLsizei, buf: *mut GLchar) {
+    let len = if buf_size <= 0 { 0 } else { ::std::cmp::min(info_log.len(), buf_size as usize - 1) };
+    if buf_size > 0 {
+        ptr::copy_nonoverlapping(info_log.as_ptr() as *const GLchar, buf, len);
+        *buf.offset(len as isize) = 0;
+    }
+    if !length.is_null() {
+        *length = len as GLsizei;
+    }
+}
+
+extern "system" fn get_error_() -> GLenum {
+    with_current_("glGetError", |state| {
+        if state.errors.is_empty() { gl::NO_ERROR } else { state.errors.remove(0) }
+    })
+}
+extern "system" fn get_string_(name: GLenum) -> *const GLubyte {
+    with_current_("glGetString", |state| {
+        let res: &[u8] = match name {
+            gl::VENDOR => b"scgl\0",
+            gl::RENDERER => b"scgl fake\0",
+            gl::VERSION => return state.version_.as_ptr() as *const GLubyte,
+            gl::SHADING_LANGUAGE_VERSION => b"1.10 scgl fake\0",
+            gl::EXTENSIONS => return state.extensions_.as_ptr() as *const GLubyte,
+            _ => {
+                state.raise_(gl::INVALID_ENUM);
+                return ptr::null();
+            },
+        };
+        res.as_ptr()
+    })
+}
+extern "system" fn get_integerv_(pname: GLenum, data: *mut GLint) {
+    with_current_("glGetIntegerv", |state| {
+        let val = match pname {
+            gl::ARRAY_BUFFER_BINDING => state.bound_buffers.get(&gl::ARRAY_BUFFER).cloned().unwrap_or(0),
+            gl::ELEMENT_ARRAY_BUFFER_BINDING => state.bound_buffers.get(&gl::ELEMENT_ARRAY_BUFFER).cloned().unwrap_or(0),
+            VERTEX_ARRAY_BINDING_ => state.bound_vertex_array,
+            gl::MAX_TEXTURE_SIZE => 2048,
+            gl::MAX_VERTEX_ATTRIBS => 16,
+            gl::MAX_TEXTURE_IMAGE_UNITS => 8,
+            _ => return state.raise_(gl::INVALID_ENUM),
+        };
+        unsafe { *data = val as GLint; }
+    })
+}
+extern "system" fn gen_buffers_(n: GLsizei, buffers: *mut GLuint) {
+    with_current_("glGenBuffers", |state| {
+        let objects = state.objects.clone();
+        if n < 0 {
+            return state.raise_(gl::INVALID_VALUE);
+        }
+        let mut objects = objects.lock().unwrap();
+        for i in 0..n as isize {
+            let name = objects.gen_name_();
+            objects.buffers.insert(name, FakeBuffer::default());
+            unsafe { *buffers.offset(i) = name; }
+        }
+    })
+}
+extern "system" fn delete_buffers_(n: GLsizei, buffers: *const GLuint) {
+    with_current_("glDeleteBuffers", |state| {
+        let objects = state.objects.clone();
+        if n < 0 {
+            return state.raise_(gl::INVALID_VALUE);
+        }
+        for &name in unsafe { slice::from_raw_parts(buffers, n as usize) } {
+            objects.lock().unwrap().buffers.remove(&name);
+            for bound in state.bound_buffers.values_mut() {
+                if *bound == name {
+                    *bound = 0;
+                }
+            }
+        }
+    })
+}
+extern "system" fn bind_buffer_(target: GLenum, buffer: GLuint) {
+    with_current_("glBindBuffer", |state| {
+        let objects = state.objects.clone();
+        if target != gl::ARRAY_BUFFER && target != gl::ELEMENT_ARRAY_BUFFER {
+            return state.raise_(gl::INVALID_ENUM);
+        }
+        if buffer != 0 && !objects.lock().unwrap().buffers.contains_key(&buffer) {
+            return state.raise_(gl::INVALID_OPERATION);
+        }
+        state.bound_buffers.insert(target, buffer);
+    })
+}
+extern "system" fn buffer_data_(target: GLenum, size: GLsizeiptr, data: *const c_void, usage: GLenum) {
+    with_current_("glBufferData", |state| {
+        let objects = state.objects.clone();
+        if size < 0 {
+            return state.raise_(gl::INVALID_VALUE);
+        }
+        let bound = match state.bound_buffers.get(&target) {
+            Some(&bound) if bound != 0 => bound,
+            Some(_) => return state.raise_(gl::INVALID_OPERATION),
+            None => return state.raise_(gl::INVALID_ENUM),
+        };
+        let mut objects = objects.lock().unwrap();
+        let buffer = objects.buffers.get_mut(&bound).unwrap();
+        if buffer.storage_flags.is_some() {
+            return state.raise_(gl::INVALID_OPERATION);
+        }
+        buffer.data = if data.is_null() {
+            vec![0; size as usize]
+        } else {
+            unsafe { slice::from_raw_parts(data as *const u8, size as usize).to_vec() }
+        };
+        buffer.usage = usage;
+    })
+}
+extern "system" fn buffer_storage_(target: GLenum, size: GLsizeiptr, data: *const c_void, flags: GLbitfield) {
+    with_current_("glBufferStorage", |state| {
+        let objects = state.objects.clone();
+        if size <= 0 {
+            return state.raise_(gl::INVALID_VALUE);
+        }
+        let bound = match state.bound_buffers.get(&target) {
+            Some(&bound) if bound != 0 => bound,
+            Some(_) => return state.raise_(gl::INVALID_OPERATION),
+            None => return state.raise_(gl::INVALID_ENUM),
+        };
+        let mut objects = objects.lock().unwrap();
+        let buffer = objects.buffers.get_mut(&bound).unwrap();
+        if buffer.storage_flags.is_some() {
+            return state.raise_(gl::INVALID_OPERATION);
+        }
+        buffer.data = if data.is_null() {
+            vec![0; size as usize]
+        } else {
+            unsafe { slice::from_raw_parts(data as *const u8, size as usize).to_vec() }
+        };
+        buffer.storage_flags = Some(flags);
+    })
+}
+extern "system" fn gen_vertex_arrays_(n: GLsizei, arrays: *mut GLuint) {
+    with_current_("glGenVertexArrays", |state| {
+        if n < 0 {
+            return state.raise_(gl::INVALID_VALUE);
+        }
+        for i in 0..n as isize {
+            state.next_vertex_array_name_ += 1;
+            let name = state.next_vertex_array_name_;
+            state.vertex_arrays.insert(name);
+            unsafe { *arrays.offset(i) = name; }
+        }
+    })
+}
+extern "system" fn bind_vertex_array_(array: GLuint) {
+    with_current_("glBindVertexArray", |state| {
+        if array != 0 && !state.vertex_arrays.contains(&array) {
+            return state.raise_(gl::INVALID_OPERATION);
+        }
+        state.bound_vertex_array = array;
+    })
+}
+extern "system" fn delete_vertex_arrays_(n: GLsizei, arrays: *const GLuint) {
+    with_current_("glDeleteVertexArrays", |state| {
+        if n < 0 {
+            return state.raise_(gl::INVALID_VALUE);
+        }
+        for &name in unsafe { slice::from_raw_parts(arrays, n as usize) } {
+            state.vertex_arrays.remove(&name);
+            if state.bound_vertex_array == name {
+                state.bound_vertex_array = 0;
+            }
+        }
+    })
+}
+extern "system" fn create_shader_(type_: GLenum) -> GLuint {
+    with_current_("glCreateShader", |state| {
+        let objects = state.objects.clone();
+        if type_ != gl::VERTEX_SHADER && type_ != gl::FRAGMENT_SHADER {
+            state.raise_(gl::INVALID_ENUM);
+            return 0;
+        }
+        let mut objects = objects.lock().unwrap();
+        let name = objects.gen_name_();
+        objects.shaders.insert(name, FakeShader { type_: type_, .. FakeShader::default() });
+        name
+    })
+}
+extern "system" fn shader_source_(shader: GLuint, count: GLsizei, strings: *const *const GLchar, lengths: *const GLint) {
+    with_current_("glShaderSource", |state| {
+        let objects = state.objects.clone();
+        if count < 0 {
+            return state.raise_(gl::INVALID_VALUE);
+        }
+        let mut source = String::new();
+        for i in 0..count as isize {
+            unsafe {
+                let string = *strings.offset(i);
+                let bytes = if lengths.is_null() || *lengths.offset(i) < 0 {
+                    CStr::from_ptr(string as *const c_char).to_bytes()
+                } else {
+                    slice::from_raw_parts(string as *const u8, *lengths.offset(i) as usize)
+                };
+                source.push_str(&String::from_utf8_lossy(bytes));
+            }
+        }
+        match objects.lock().unwrap().shaders.get_mut(&shader) {
+            Some(fake_shader) => fake_shader.source = source,
+            None => return state.raise_(gl::INVALID_VALUE),
+        };
+    })
+}
+extern "system" fn compile_shader_(shader: GLuint) {
+    with_current_("glCompileShader", |state| {
+        let objects = state.objects.clone();
+        match objects.lock().unwrap().shaders.get_mut(&shader) {
+            Some(fake_shader) => {
+                let error_line = fake_shader.source.lines().position(|line| line.trim().starts_with("#error"));
+                fake_shader.compiled = error_line.is_none();
+                fake_shader.info_log = match error_line {
+                    Some(line) => format!("0:{}(1): error: #error directive\n", line + 1),
+                    None => String::new(),
+                };
+            },
+            None => return state.raise_(gl::INVALID_VALUE),
+        };
+    })
+}
+extern "system" fn get_shaderiv_(shader: GLuint, pname: GLenum, params: *mut GLint) {
+    with_current_("glGetShaderiv", |state| {
+        let objects = state.objects.clone();
+        let val = match objects.lock().unwrap().shaders.get(&shader) {
+            Some(fake_shader) => match pname {
+                gl::SHADER_TYPE => fake_shader.type_ as GLint,
+                gl::COMPILE_STATUS => fake_shader.compiled as GLint,
+                gl::INFO_LOG_LENGTH => if fake_shader.info_log.is_empty() { 0 } else { fake_shader.info_log.len() as GLint + 1 },
+                gl::SHADER_SOURCE_LENGTH => fake_shader.source.len() as GLint + 1,
+                _ => return state.raise_(gl::INVALID_ENUM),
+            },
+            None => return state.raise_(gl::INVALID_VALUE),
+        };
+        unsafe { *params = val; }
+    })
+}
+extern "system" fn get_shader_info_log_(shader: GLuint, buf_size: GLsizei, length: *mut GLsizei, info_log: *mut GLchar) {
+    with_current_("glGetShaderInfoLog", |state| {
+        let objects = state.objects.clone();
+        match objects.lock().unwrap().shaders.get(&shader) {
+            Some(fake_shader) => unsafe { copy_info_log_(&fake_shader.info_log, buf_size, length, info_log) },
+            None => return state.raise_(gl::INVALID_VALUE),
+        };
+    })
+}
+extern "system" fn delete_shader_(shader: GLuint) {
+    with_current_("glDeleteShader", |state| {
+        let objects = state.objects.clone();
+        objects.lock().unwrap().shaders.remove(&shader);
+    })
+}
+extern "system" fn create_program_() -> GLuint {
+    with_current_("glCreateProgram", |state| {
+        let objects = state.objects.clone();
+        let mut objects = objects.lock().unwrap();
+        let name = objects.gen_name_();
+        objects.programs.insert(name, FakeProgram::default());
+        name
+    })
+}
+extern "system" fn attach_shader_(program: GLuint, shader: GLuint) {
+    with_current_("glAttachShader", |state| {
+        let objects = state.objects.clone();
+        let mut objects = objects.lock().unwrap();
+        if !objects.shaders.contains_key(&shader) {
+            return state.raise_(gl::INVALID_VALUE);
+        }
+        match objects.programs.get_mut(&program) {
+            Some(fake_program) => fake_program.shaders.push(shader),
+            None => return state.raise_(gl::INVALID_VALUE),
+        }
+    })
+}
+extern "system" fn link_program_(program: GLuint) {
+    with_current_("glLinkProgram", |state| {
+        let objects = state.objects.clone();
+        let mut objects = objects.lock().unwrap();
+        let (has_vertex_shader, has_fragment_shader) = match objects.programs.get(&program) {
+            Some(fake_program) => {
+                let has_compiled = |type_: GLenum| fake_program.shaders.iter().any(|shader| {
+                    objects.shaders.get(shader).map_or(false, |fake_shader| fake_shader.type_ == type_ && fake_shader.compiled)
+                });
+                (has_compiled(gl::VERTEX_SHADER), has_compiled(gl::FRAGMENT_SHADER))
+            },
+            None => return state.raise_(gl::INVALID_VALUE),
+        };
+        let fake_program = objects.programs.get_mut(&program).unwrap();
+        fake_program.linked = has_vertex_shader && has_fragment_shader;
+        fake_program.info_log = if !has_vertex_shader {
+            "error: no compiled vertex shader attached\n".to_string()
+        } else if !has_fragment_shader {
+            "error: no compiled fragment shader attached\n".to_string()
+        } else {
+            String::new()
+        };
+    })
+}
+extern "system" fn get_programiv_(program: GLuint, pname: GLenum, params: *mut GLint) {
+    with_current_("glGetProgramiv", |state| {
+        let objects = state.objects.clone();
+        let val = match objects.lock().unwrap().programs.get(&program) {
+            Some(fake_program) => match pname {
+                gl::LINK_STATUS => fake_program.linked as GLint,
+                gl::VALIDATE_STATUS => fake_program.validated as GLint,
+                gl::ATTACHED_SHADERS => fake_program.shaders.len() as GLint,
+                gl::INFO_LOG_LENGTH => if fake_program.info_log.is_empty() { 0 } else { fake_program.info_log.len() as GLint + 1 },
+                _ => return state.raise_(gl::INVALID_ENUM),
+            },
+            None => return state.raise_(gl::INVALID_VALUE),
+        };
+        unsafe { *params = val; }
+    })
+}
+extern "system" fn get_program_info_log_(program: GLuint, buf_size: GLsizei, length: *mut GLsizei, info_log: *mut GLchar) {
+    with_current_("glGetProgramInfoLog", |state| {
+        let objects = state.objects.clone();
+        match objects.lock().unwrap().programs.get(&program) {
+            Some(fake_program) => unsafe { copy_info_log_(&fake_program.info_log, buf_size, length, info_log) },
+            None => return state.raise_(gl::INVALID_VALUE),
+        };
+    })
+}
+extern "system" fn validate_program_(program: GLuint) {
+    with_current_("glValidateProgram", |state| {
+        let objects = state.objects.clone();
+        match objects.lock().unwrap().programs.get_mut(&program) {
+            Some(fake_program) => {
+                fake_program.validated = fake_program.linked;
+                if !fake_program.linked {
+                    fake_program.info_log = "error: program is not linked\n".to_string();
+                }
+            },
+            None => return state.raise_(gl::INVALID_VALUE),
+        };
+    })
+}
+extern "system" fn delete_program_(program: GLuint) {
+    with_current_("glDeleteProgram", |state| {
+        let objects = state.objects.clone();
+        objects.lock().unwrap().programs.remove(&program);
+    })
+}
+extern "system" fn get_graphics_reset_status_() -> GLenum {
+    with_current_("glGetGraphicsResetStatus", |state| state.reset_status)
+}
+extern "system" fn finish_() {
+    with_current_("glFinish", |_| {})
+}
+extern "system" fn enable_(cap: GLenum) {
+    with_current_("glEnable", |state| {
+        state.enabled.insert(cap);
+    })
+}
+extern "system" fn disable_(cap: GLenum) {
+    with_current_("glDisable", |state| {
+        state.enabled.remove(&cap);
+    })
+}
+extern "system" fn debug_message_callback_(callback: GLDEBUGPROC, user_param: *const c_void) {
+    with_current_("glDebugMessageCallback", |state| {
+        state.debug_callback = Some((callback, user_param));
+    })
+}
+extern "system" fn debug_message_control_(source: GLenum, type_: GLenum, severity: GLenum, count: GLsizei, _: *const GLuint, enabled: GLboolean) {
+    with_current_("glDebugMessageControl", |state| {
+        // Only toggling all messages is simulated.
+        if source == gl::DONT_CARE && type_ == gl::DONT_CARE && severity == gl::DONT_CARE && count == 0 {
+            state.debug_messages_enabled = enabled == gl::TRUE;
+        }
+    })
+}
+extern "system" fn push_debug_group_(_: GLenum, _: GLuint, length: GLsizei, message: *const GLchar) {
+    with_current_("glPushDebugGroup", |state| {
+        let message = unsafe {
+            if length < 0 {
+                CStr::from_ptr(message as *const c_char).to_bytes()
+            } else {
+                slice::from_raw_parts(message as *const u8, length as usize)
+            }
+        };
+        state.debug_groups.push(String::from_utf8_lossy(message).into_owned());
+    })
+}
+extern "system" fn pop_debug_group_() {
+    with_current_("glPopDebugGroup", |state| {
+        if state.debug_groups.pop().is_none() {
+            state.raise_(gl::STACK_UNDERFLOW);
+        }
+    })
+}
+extern "system" fn object_label_(identifier: GLenum, name: GLuint, length: GLsizei, label: *const GLchar) {
+    with_current_("glObjectLabel", |state| {
+        let objects = state.objects.clone();
+        let mut objects = objects.lock().unwrap();
+        if !objects.contains_(identifier, name) {
+            return state.raise_(gl::INVALID_VALUE);
+        }
+        let label = unsafe {
+            if length < 0 {
+                CStr::from_ptr(label as *const c_char).to_bytes()
+            } else {
+                slice::from_raw_parts(label as *const u8, length as usize)
+            }
+        };
+        objects.labels.insert((identifier, name), String::from_utf8_lossy(label).into_owned());
+    })
+}
+
+/// Get the address of a procedure of the fake opengl implementation.
+/// Returns null for the procedures which are not implemented.
+pub fn fake_proc_address(name: &str) -> *const c_void {
+    match name {
+        "glGetError" => get_error_ as *const c_void,
+        "glGetString" => get_string_ as *const c_void,
+        "glGetIntegerv" => get_integerv_ as *const c_void,
+        "glGenBuffers" => gen_buffers_ as *const c_void,
+        "glDeleteBuffers" => delete_buffers_ as *const c_void,
+        "glBindBuffer" => bind_buffer_ as *const c_void,
+        "glBufferData" => buffer_data_ as *const c_void,
+        "glBufferStorage" => buffer_storage_ as *const c_void,
+        "glGenVertexArrays" => gen_vertex_arrays_ as *const c_void,
+        "glBindVertexArray" => bind_vertex_array_ as *const c_void,
+        "glDeleteVertexArrays" => delete_vertex_arrays_ as *const c_void,
+        "glCreateShader" => create_shader_ as *const c_void,
+        "glShaderSource" => shader_source_ as *const c_void,
+        "glCompileShader" => compile_shader_ as *const c_void,
+        "glGetShaderiv" => get_shaderiv_ as *const c_void,
+        "glGetShaderInfoLog" => get_shader_info_log_ as *const c_void,
+        "glDeleteShader" => delete_shader_ as *const c_void,
+        "glCreateProgram" => create_program_ as *const c_void,
+        "glAttachShader" => attach_shader_ as *const c_void,
+        "glLinkProgram" => link_program_ as *const c_void,
+        "glGetProgramiv" => get_programiv_ as *const c_void,
+        "glGetProgramInfoLog" => get_program_info_log_ as *const c_void,
+        "glValidateProgram" => validate_program_ as *const c_void,
+        "glDeleteProgram" => delete_program_ as *const c_void,
+        "glFinish" => finish_ as *const c_void,
+        "glEnable" => enable_ as *const c_void,
+        "glDisable" => disable_ as *const c_void,
+        "glDebugMessageCallback" => debug_message_callback_ as *const c_void,
+        "glDebugMessageControl" => debug_message_control_ as *const c_void,
+        "glObjectLabel" => object_label_ as *const c_void,
+        "glPushDebugGroup" => push_debug_group_ as *const c_void,
+        "glPopDebugGroup" => pop_debug_group_ as *const c_void,
+        "glGetGraphicsResetStatus" => get_graphics_reset_status_ as *const c_void,
+        _ => ptr::null(),
+    }
+}
+
+/// Creation of the api backend of the fake opengl implementation.
+pub struct FakeMakeApiBackend;
+unsafe impl MakeApiBackend for FakeMakeApiBackend {
+    unsafe fn get_proc_address(&mut self, name: &str) -> *const c_void {
+        fake_proc_address(name)
+    }
+    unsafe fn into_backend(self) -> Box<ApiBackend> {
+        Box::new(FakeApiBackend)
+    }
+}
+
+/// Api backend of the fake opengl implementation.
+pub struct FakeApiBackend;
+unsafe impl ApiBackend for FakeApiBackend {
+    unsafe fn clear_current_context(&self) -> Result<(), Error> {
+        CURRENT_FAKE_CONTEXT_.with(|current| *current.borrow_mut() = None);
+        Ok(())
+    }
+}
+
+/// Context backend of the fake opengl implementation.
+pub struct FakeContextBackend {
+    state_: Rc<RefCell<FakeContextState>>,
+}
+impl FakeContextBackend {
+    /// Create a context with its own objects.
+    pub fn new() -> Self {
+        FakeContextBackend {
+            state_: Rc::new(RefCell::new(FakeContextState::new(Arc::new(Mutex::new(FakeObjects::default()))))),
+        }
+    }
+    /// Create a context sharing the objects with `other`.
+    pub fn new_shared(other: &FakeContextBackend) -> Self {
+        FakeContextBackend {
+            state_: Rc::new(RefCell::new(FakeContextState::new(other.state_.borrow().objects.clone()))),
+        }
+    }
+    /// Get the state for assertions. It stays accessible after the backend was moved into a
+    /// context.
+    pub fn state(&self) -> Rc<RefCell<FakeContextState>> {
+        self.state_.clone()
+    }
+}
+unsafe impl ContextBackend for FakeContextBackend {
+    fn is_current(&self) -> bool {
+        CURRENT_FAKE_CONTEXT_.with(|current| {
+            current.borrow().as_ref().map_or(false, |current| Rc::ptr_eq(current, &self.state_))
+        })
+    }
+    unsafe fn make_current(&self) -> Result<(), Error> {
+        if self.state_.borrow().make_current_fails {
+            return Err(Error::ContextLost);
+        }
+        CURRENT_FAKE_CONTEXT_.with(|current| *current.borrow_mut() = Some(self.state_.clone()));
+        Ok(())
+    }
+}
+
+/// Sharing group backend of the fake opengl implementation.
+/// It implements Send and Sync, so contexts of the group can be created in every thread.
+pub struct FakeSharingGroupBackend {
+    objects_: Arc<Mutex<FakeObjects>>,
+}
+impl FakeSharingGroupBackend {
+    pub fn new() -> Self {
+        FakeSharingGroupBackend {
+            objects_: Arc::new(Mutex::new(FakeObjects::default())),
+        }
+    }
+    /// Create a context of the group, whose state stays accessible for assertions.
+    pub fn create_context_backend(&self) -> FakeContextBackend {
+        FakeContextBackend {
+            state_: Rc::new(RefCell::new(FakeContextState::new(self.objects_.clone()))),
+        }
+    }
+}
+unsafe impl SharingGroupBackend for FakeSharingGroupBackend {
+    unsafe fn create_context(&self) -> Result<Box<ContextBackend>, Error> {
+        Ok(Box::new(self.create_context_backend()))
+    }
+}
@@ -1,7 +1,81 @@
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+/// The bindings of opengl es are generated as Gles2, but used as Gl by this library.
+#[cfg(any(feature = "gles2", feature = "gles3"))]
+pub use self::Gles2 as Gl;
+
+/// Error values which are not part of every generated binding.
+const STACK_OVERFLOW_: types::GLenum = 0x0503;
+const STACK_UNDERFLOW_: types::GLenum = 0x0504;
+const INVALID_FRAMEBUFFER_OPERATION_: types::GLenum = 0x0506;
+const CONTEXT_LOST_: types::GLenum = 0x0507;
+
 /// OpenGl error.
-#[derive(Debug)]
+/// Each variant corresponds to a value returned by glGetError.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Error {
-    // TODO
+    /// GL_INVALID_ENUM.
+    InvalidEnum,
+    /// GL_INVALID_VALUE.
+    InvalidValue,
+    /// GL_INVALID_OPERATION.
+    InvalidOperation,
+    /// GL_STACK_OVERFLOW.
+    StackOverflow,
+    /// GL_STACK_UNDERFLOW.
+    StackUnderflow,
+    /// GL_OUT_OF_MEMORY.
+    OutOfMemory,
+    /// GL_INVALID_FRAMEBUFFER_OPERATION.
+    InvalidFramebufferOperation,
+    /// GL_CONTEXT_LOST.
+    ContextLost,
+    /// An error value unknown to this library.
+    Unknown(types::GLenum),
+}
+impl Error {
+    /// Get the error from a value returned by glGetError. Returns None for GL_NO_ERROR.
+    pub fn from_enum_val(enum_val: types::GLenum) -> Option<Self> {
+        match enum_val {
+            NO_ERROR => None,
+            INVALID_ENUM => Some(Error::InvalidEnum),
+            INVALID_VALUE => Some(Error::InvalidValue),
+            INVALID_OPERATION => Some(Error::InvalidOperation),
+            STACK_OVERFLOW_ => Some(Error::StackOverflow),
+            STACK_UNDERFLOW_ => Some(Error::StackUnderflow),
+            OUT_OF_MEMORY => Some(Error::OutOfMemory),
+            INVALID_FRAMEBUFFER_OPERATION_ => Some(Error::InvalidFramebufferOperation),
+            CONTEXT_LOST_ => Some(Error::ContextLost),
+            _ => Some(Error::Unknown(enum_val)),
+        }
+    }
+    /// Get the value returned by glGetError.
+    pub fn enum_val(&self) -> types::GLenum {
+        match *self {
+            Error::InvalidEnum => INVALID_ENUM,
+            Error::InvalidValue => INVALID_VALUE,
+            Error::InvalidOperation => INVALID_OPERATION,
+            Error::StackOverflow => STACK_OVERFLOW_,
+            Error::StackUnderflow => STACK_UNDERFLOW_,
+            Error::OutOfMemory => OUT_OF_MEMORY,
+            Error::InvalidFramebufferOperation => INVALID_FRAMEBUFFER_OPERATION_,
+            Error::ContextLost => CONTEXT_LOST_,
+            Error::Unknown(enum_val) => enum_val,
+        }
+    }
+}
+impl ::std::fmt::Display for Error {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            Error::InvalidEnum => write!(f, "GL_INVALID_ENUM"),
+            Error::InvalidValue => write!(f, "GL_INVALID_VALUE"),
+            Error::InvalidOperation => write!(f, "GL_INVALID_OPERATION"),
+            Error::StackOverflow => write!(f, "GL_STACK_OVERFLOW"),
+            Error::StackUnderflow => write!(f, "GL_STACK_UNDERFLOW"),
+            Error::OutOfMemory => write!(f, "GL_OUT_OF_MEMORY"),
+            Error::InvalidFramebufferOperation => write!(f, "GL_INVALID_FRAMEBUFFER_OPERATION"),
+            Error::ContextLost => write!(f, "GL_CONTEXT_LOST"),
+            Error::Unknown(enum_val) => write!(f, "unknown error 0x{:04x}", enum_val),
+        }
+    }
 }
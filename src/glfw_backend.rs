@@ -1,16 +1,115 @@
 use glfw;
-use std::ops::Deref;
 use std::os::raw::c_void;
-use std::sync::{Arc, Mutex};
+use std::sync::mpsc::Receiver;
 use std::rc::Rc;
-use std::cell::UnsafeCell;
-use std::mem::transmute;
-use std::io::Write;
-use std::io::stderr;
+use std::ptr;
 
 use Error;
 use ApiBackend;
 use MakeApiBackend;
-use Api;
 use ContextBackend;
-use Context;
+use ContextConfig;
+use ContextProfile;
+
+/// Get the window hints of the config.
+/// no_error is ignored, because glfw has no hint for it in this version, and a context with error
+/// checks is a valid substitute.
+pub fn glfw_window_hints(context_config: &ContextConfig) -> Vec<glfw::WindowHint> {
+    let client_api = match context_config.profile {
+        ContextProfile::Core | ContextProfile::Compatibility => glfw::ClientApiHint::OpenGl,
+        ContextProfile::Es => glfw::ClientApiHint::OpenGlEs,
+    };
+    let profile = match context_config.profile {
+        _ if !context_config.has_profile() => glfw::OpenGlProfileHint::Any,
+        ContextProfile::Compatibility => glfw::OpenGlProfileHint::Compat,
+        _ => glfw::OpenGlProfileHint::Core,
+    };
+    let robustness = if context_config.robust {
+        glfw::ContextRobustnessHint::LoseContextOnReset
+    } else {
+        glfw::ContextRobustnessHint::NoRobustness
+    };
+    vec![
+        glfw::WindowHint::ClientApi(client_api),
+        glfw::WindowHint::ContextVersion(context_config.version.major, context_config.version.minor),
+        glfw::WindowHint::OpenGlProfile(profile),
+        glfw::WindowHint::OpenGlForwardCompat(context_config.forward_compatible && context_config.profile != ContextProfile::Es),
+        glfw::WindowHint::OpenGlDebugContext(context_config.debug),
+        glfw::WindowHint::ContextRobustness(robustness),
+        glfw::WindowHint::SRgbCapable(context_config.srgb),
+        glfw::WindowHint::DepthBits(context_config.depth_bits as u32),
+        glfw::WindowHint::StencilBits(context_config.stencil_bits as u32),
+        glfw::WindowHint::Samples(context_config.samples as u32),
+    ]
+}
+
+/// Set the window hints of the config, which are used by the windows created afterwards.
+pub fn set_glfw_window_hints(glfw: &mut glfw::Glfw, context_config: &ContextConfig) {
+    for hint in glfw_window_hints(context_config) {
+        glfw.window_hint(hint);
+    }
+}
+
+/// Create a window with a context of the config.
+/// Returns Error::Unsupported if glfw could not create the window, for example because the
+/// version is not supported.
+pub fn create_glfw_window(glfw: &mut glfw::Glfw, context_config: &ContextConfig, width: u32, height: u32, title: &str) -> Result<(glfw::Window, Receiver<(f64, glfw::WindowEvent)>), Error> {
+    set_glfw_window_hints(glfw, context_config);
+    glfw.create_window(width, height, title, glfw::WindowMode::Windowed)
+        .ok_or(Error::Unsupported("a glfw window of the context config"))
+}
+
+/// Creation of an api backend using glfw.
+/// The context of the window has to be current.
+pub struct GlfwMakeApiBackend<'w> {
+    window_: &'w mut glfw::Window,
+}
+impl<'w> GlfwMakeApiBackend<'w> {
+    pub fn new(window: &'w mut glfw::Window) -> Self {
+        GlfwMakeApiBackend {
+            window_: window,
+        }
+    }
+}
+unsafe impl<'w> MakeApiBackend for GlfwMakeApiBackend<'w> {
+    unsafe fn get_proc_address(&mut self, name: &str) -> *const c_void {
+        self.window_.get_proc_address(name) as *const c_void
+    }
+    unsafe fn into_backend(self) -> Box<ApiBackend> {
+        Box::new(GlfwApiBackend)
+    }
+}
+
+/// Api backend using glfw.
+pub struct GlfwApiBackend;
+unsafe impl ApiBackend for GlfwApiBackend {
+    unsafe fn clear_current_context(&self) -> Result<(), Error> {
+        glfw::ffi::glfwMakeContextCurrent(ptr::null_mut());
+        Ok(())
+    }
+}
+
+/// Context backend using the context of a glfw window.
+pub struct GlfwContextBackend {
+    window_: Rc<glfw::Window>,
+}
+impl GlfwContextBackend {
+    pub fn new(window: Rc<glfw::Window>) -> Self {
+        GlfwContextBackend {
+            window_: window,
+        }
+    }
+    /// Get the window.
+    pub fn window(&self) -> &Rc<glfw::Window> {
+        &self.window_
+    }
+}
+unsafe impl ContextBackend for GlfwContextBackend {
+    fn is_current(&self) -> bool {
+        glfw::Context::is_current(&*self.window_)
+    }
+    unsafe fn make_current(&self) -> Result<(), Error> {
+        glfw::ffi::glfwMakeContextCurrent(glfw::Context::window_ptr(&*self.window_));
+        Ok(())
+    }
+}
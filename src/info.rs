@@ -0,0 +1,217 @@
+use std::os::raw::c_char;
+use std::ffi::CStr;
+use std::collections::HashSet;
+use std::fmt;
+
+use gl;
+use gl::types::{GLenum, GLint};
+
+use CurrentContext;
+
+/// Limits which are not part of every generated binding.
+const MAX_TEXTURE_SIZE_: GLenum = 0x0D33;
+const MAX_VIEWPORT_DIMS_: GLenum = 0x0D3A;
+const MAX_CUBE_MAP_TEXTURE_SIZE_: GLenum = 0x851C;
+const MAX_VERTEX_ATTRIBS_: GLenum = 0x8869;
+const MAX_TEXTURE_IMAGE_UNITS_: GLenum = 0x8872;
+const MAX_VERTEX_TEXTURE_IMAGE_UNITS_: GLenum = 0x8B4C;
+const MAX_COMBINED_TEXTURE_IMAGE_UNITS_: GLenum = 0x8B4D;
+const MAX_RENDERBUFFER_SIZE_: GLenum = 0x84E8;
+const MAX_DRAW_BUFFERS_: GLenum = 0x8824;
+const MAX_COLOR_ATTACHMENTS_: GLenum = 0x8CDF;
+const MAX_SAMPLES_: GLenum = 0x8D57;
+const MAX_UNIFORM_BLOCK_SIZE_: GLenum = 0x8A30;
+const MAX_UNIFORM_BUFFER_BINDINGS_: GLenum = 0x8A2F;
+#[cfg(any(feature = "gl33", feature = "gl45", feature = "gles3"))]
+const NUM_EXTENSIONS_: GLenum = 0x821D;
+
+/// Version of opengl or of the shading language.
+/// The minor version is the number after the dot, so GLSL 1.10 is 1.10 and not 1.1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+}
+impl Version {
+    pub fn new(major: u32, minor: u32) -> Self {
+        Version {
+            major: major,
+            minor: minor,
+        }
+    }
+    /// Parse the version of a string returned by glGetString(GL_VERSION) or
+    /// glGetString(GL_SHADING_LANGUAGE_VERSION), for example "4.5.0 NVIDIA 390.48" or
+    /// "OpenGL ES GLSL ES 3.00".
+    pub fn parse(version_string: &str) -> Option<Self> {
+        version_string.split_whitespace()
+            .filter(|word| word.starts_with(|c: char| c.is_digit(10)))
+            .filter_map(|word| {
+                let mut numbers = word.split('.');
+                let major = numbers.next().and_then(|major| major.parse().ok());
+                let minor = numbers.next().and_then(|minor| minor.parse().ok());
+                match (major, minor) {
+                    (Some(major), Some(minor)) => Some(Version::new(major, minor)),
+                    _ => None,
+                }
+            })
+            .next()
+    }
+}
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Implementation limits of a context.
+/// Limits which can not be queried with the version or extensions of the context are None.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// GL_MAX_TEXTURE_SIZE.
+    pub max_texture_size: GLint,
+    /// GL_MAX_CUBE_MAP_TEXTURE_SIZE.
+    pub max_cube_map_texture_size: GLint,
+    /// GL_MAX_VIEWPORT_DIMS.
+    pub max_viewport_dims: [GLint; 2],
+    /// GL_MAX_VERTEX_ATTRIBS.
+    pub max_vertex_attribs: GLint,
+    /// GL_MAX_TEXTURE_IMAGE_UNITS.
+    pub max_texture_image_units: GLint,
+    /// GL_MAX_VERTEX_TEXTURE_IMAGE_UNITS.
+    pub max_vertex_texture_image_units: GLint,
+    /// GL_MAX_COMBINED_TEXTURE_IMAGE_UNITS.
+    pub max_combined_texture_image_units: GLint,
+    /// GL_MAX_RENDERBUFFER_SIZE.
+    pub max_renderbuffer_size: Option<GLint>,
+    /// GL_MAX_DRAW_BUFFERS.
+    pub max_draw_buffers: Option<GLint>,
+    /// GL_MAX_COLOR_ATTACHMENTS.
+    pub max_color_attachments: Option<GLint>,
+    /// GL_MAX_SAMPLES.
+    pub max_samples: Option<GLint>,
+    /// GL_MAX_UNIFORM_BLOCK_SIZE.
+    pub max_uniform_block_size: Option<GLint>,
+    /// GL_MAX_UNIFORM_BUFFER_BINDINGS.
+    pub max_uniform_buffer_bindings: Option<GLint>,
+}
+impl Limits {
+    /// Query the limits of the current context.
+    fn query_(current_context: &CurrentContext) -> Self {
+        let get = |pname| get_integers_::<[GLint; 1]>(current_context, pname).map(|val| val[0]);
+        Limits {
+            max_texture_size: get(MAX_TEXTURE_SIZE_).unwrap_or(0),
+            max_cube_map_texture_size: get(MAX_CUBE_MAP_TEXTURE_SIZE_).unwrap_or(0),
+            max_viewport_dims: get_integers_::<[GLint; 2]>(current_context, MAX_VIEWPORT_DIMS_).unwrap_or([0, 0]),
+            max_vertex_attribs: get(MAX_VERTEX_ATTRIBS_).unwrap_or(0),
+            max_texture_image_units: get(MAX_TEXTURE_IMAGE_UNITS_).unwrap_or(0),
+            max_vertex_texture_image_units: get(MAX_VERTEX_TEXTURE_IMAGE_UNITS_).unwrap_or(0),
+            max_combined_texture_image_units: get(MAX_COMBINED_TEXTURE_IMAGE_UNITS_).unwrap_or(0),
+            max_renderbuffer_size: get(MAX_RENDERBUFFER_SIZE_),
+            max_draw_buffers: get(MAX_DRAW_BUFFERS_),
+            max_color_attachments: get(MAX_COLOR_ATTACHMENTS_),
+            max_samples: get(MAX_SAMPLES_),
+            max_uniform_block_size: get(MAX_UNIFORM_BLOCK_SIZE_),
+            max_uniform_buffer_bindings: get(MAX_UNIFORM_BUFFER_BINDINGS_),
+        }
+    }
+}
+
+/// Query integers using glGetIntegerv. Returns None if the query raised an error, which is the
+/// case if the parameter is not supported.
+fn get_integers_<A: AsMut<[GLint]> + Default>(current_context: &CurrentContext, pname: GLenum) -> Option<A> {
+    let mut val = A::default();
+    unsafe {
+        current_context.gl().GetIntegerv(pname, val.as_mut().as_mut_ptr());
+    }
+    match current_context.drain_errors("glGetIntegerv") {
+        Ok(()) => Some(val),
+        Err(_) => None,
+    }
+}
+
+/// Query a string using glGetString. Returns None if the query raised an error.
+fn get_string_(current_context: &CurrentContext, name: GLenum) -> Option<String> {
+    unsafe {
+        let string = current_context.gl().GetString(name);
+        if current_context.drain_errors("glGetString").is_err() || string.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr(string as *const c_char).to_string_lossy().into_owned())
+    }
+}
+
+/// Query the extensions using glGetStringi, which is required by core profiles.
+#[cfg(any(feature = "gl33", feature = "gl45", feature = "gles3"))]
+fn get_indexed_extensions_(current_context: &CurrentContext) -> Option<HashSet<String>> {
+    if !current_context.gl().GetStringi.is_loaded() {
+        return None;
+    }
+    let num_extensions = match get_integers_::<[GLint; 1]>(current_context, NUM_EXTENSIONS_) {
+        Some(num_extensions) => num_extensions[0],
+        None => return None,
+    };
+    let mut extensions = HashSet::new();
+    for i in 0..num_extensions.max(0) {
+        unsafe {
+            let extension = current_context.gl().GetStringi(gl::EXTENSIONS, i as gl::types::GLuint);
+            if current_context.drain_errors("glGetStringi").is_err() || extension.is_null() {
+                return None;
+            }
+            extensions.insert(CStr::from_ptr(extension as *const c_char).to_string_lossy().into_owned());
+        }
+    }
+    Some(extensions)
+}
+#[cfg(not(any(feature = "gl33", feature = "gl45", feature = "gles3")))]
+fn get_indexed_extensions_(_: &CurrentContext) -> Option<HashSet<String>> {
+    None
+}
+
+/// Information about the implementation driving an api.
+#[derive(Clone, Debug)]
+pub struct ApiInfo {
+    /// The string returned by glGetString(GL_VERSION).
+    pub version_string: String,
+    /// The parsed opengl version. It is 0.0 if the version string could not be parsed.
+    pub version: Version,
+    /// The parsed shading language version, if it could be queried and parsed.
+    pub glsl_version: Option<Version>,
+    /// The string returned by glGetString(GL_VENDOR).
+    pub vendor: String,
+    /// The string returned by glGetString(GL_RENDERER).
+    pub renderer: String,
+    /// The supported extensions.
+    pub extensions: HashSet<String>,
+    /// The implementation limits.
+    pub limits: Limits,
+}
+impl ApiInfo {
+    /// Query the information of the current context.
+    /// Errors raised by the queries are drained, because they only indicate missing support.
+    pub(crate) fn query(current_context: &CurrentContext) -> Self {
+        let version_string = get_string_(current_context, gl::VERSION).unwrap_or_default();
+        let extensions = get_indexed_extensions_(current_context).unwrap_or_else(|| {
+            get_string_(current_context, gl::EXTENSIONS).unwrap_or_default()
+                .split_whitespace()
+                .map(|extension| extension.to_string())
+                .collect()
+        });
+        ApiInfo {
+            version: Version::parse(&version_string).unwrap_or(Version::new(0, 0)),
+            version_string: version_string,
+            glsl_version: get_string_(current_context, gl::SHADING_LANGUAGE_VERSION).and_then(|glsl_version| Version::parse(&glsl_version)),
+            vendor: get_string_(current_context, gl::VENDOR).unwrap_or_default(),
+            renderer: get_string_(current_context, gl::RENDERER).unwrap_or_default(),
+            extensions: extensions,
+            limits: Limits::query_(current_context),
+        }
+    }
+    /// Whether the extension `name` is supported.
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.contains(name)
+    }
+    /// Whether the version is at least `major`.`minor`.
+    pub fn version_at_least(&self, major: u32, minor: u32) -> bool {
+        self.version >= Version::new(major, minor)
+    }
+}
@@ -1,25 +1,82 @@
 mod error;
+pub use error::ObjectKind;
 pub use error::Error;
 
 mod gl;
 pub use gl::Gl;
 pub use gl::Error as GlError;
 
+mod object_registry;
+pub use object_registry::LiveObject;
+pub(crate) use object_registry::ObjectRegistry;
+
 mod api;
 pub use api::ApiBackend;
 pub use api::MakeApiBackend;
+pub use api::ErrorCheck;
+pub use api::ExternalChanges;
+pub use api::ErrorHook;
+pub use api::ApiKind;
+pub use api::LoadReport;
+pub use api::ApiBuilder;
+pub use api::ApiId;
 pub use api::Api;
 
+mod info;
+pub use info::Version;
+pub use info::Limits;
+pub use info::ApiInfo;
+
+mod version;
+pub use version::ApiVersion;
+pub use version::HasVertexArrays;
+pub use version::HasBufferStorage;
+pub use version::Gl20;
+pub use version::Gl33;
+pub use version::Gl45;
+pub use version::Gles20;
+pub use version::Gles30;
+pub use version::VersionedContext;
+
 mod context;
 pub use context::ContextBackend;
+pub use context::ContextId;
 pub use context::Context;
 
+mod context_config;
+pub use context_config::ContextProfile;
+pub use context_config::ContextConfig;
+
+mod sharing_group;
+pub use sharing_group::SharingGroupId;
+pub use sharing_group::SharingGroupBackend;
+pub use sharing_group::SharingGroup;
+
 mod state_cache;
 pub use state_cache::StateCache;
 
 mod current_context;
 pub use current_context::CurrentContext;
 pub use current_context::make_current;
+pub use current_context::make_sharing_group_current;
+pub use current_context::release_hidden_contexts;
+pub use current_context::with_context;
+pub(crate) use current_context::restore_current_context;
+
+mod robustness;
+pub use robustness::ResetStatus;
+pub(crate) use robustness::get_graphics_reset_status;
+
+mod debug;
+pub use debug::DebugSource;
+pub use debug::DebugType;
+pub use debug::DebugSeverity;
+pub use debug::DebugMessage;
+pub use debug::DebugCallback;
+pub use debug::DebugGroup;
+pub(crate) use debug::reset_debug_message_callback;
+#[cfg(feature = "log")]
+pub use debug::log_debug_message;
 
 mod buffer;
 pub use buffer::BufferTarget;
@@ -36,100 +93,1061 @@ pub use element_array_buffer::ElementArrayBufferTarget;
 pub use element_array_buffer::AsyncElementArrayBuffer;
 pub use element_array_buffer::CurrentElementArrayBuffer;
 
-/*
+#[cfg(any(feature = "gl33", feature = "gl45", feature = "gles3"))]
+mod vertex_array;
+#[cfg(any(feature = "gl33", feature = "gl45", feature = "gles3"))]
+pub use vertex_array::CurrentVertexArray;
+
 mod shader;
+pub use shader::ShaderStage;
 pub use shader::ShaderType;
-pub use shader::ShaderHandleSpecifier;
-pub use shader::ShaderHandle;
-pub use shader::ShaderFacade;
-pub use shader::Shader;
+pub use shader::ShaderDiagnostic;
+pub use shader::ShaderCompileError;
+pub use shader::parse_shader_info_log;
+pub use shader::CurrentShader;
 
 mod vertex_shader;
 pub use vertex_shader::VertexShaderType;
-pub use vertex_shader::VertexShaderHandle;
-pub use vertex_shader::VertexShaderFacade;
-pub use vertex_shader::VertexShader;
+pub use vertex_shader::CurrentVertexShader;
 
 mod fragment_shader;
 pub use fragment_shader::FragmentShaderType;
-pub use fragment_shader::FragmentShaderHandle;
-pub use fragment_shader::FragmentShaderFacade;
-pub use fragment_shader::FragmentShader;
+pub use fragment_shader::CurrentFragmentShader;
 
 mod program;
-pub use program::ProgramHandleSpecifier;
-pub use program::ProgramHandle;
-pub use program::ProgramFacade;
-pub use program::Program;
-*/
+pub use program::CurrentProgram;
+
+#[cfg(any(test, feature = "fake"))]
+mod fake_backend;
+#[cfg(any(test, feature = "fake"))]
+pub use fake_backend::FakeBuffer;
+#[cfg(any(test, feature = "fake"))]
+pub use fake_backend::FakeShader;
+#[cfg(any(test, feature = "fake"))]
+pub use fake_backend::FakeProgram;
+#[cfg(any(test, feature = "fake"))]
+pub use fake_backend::FakeObjects;
+#[cfg(any(test, feature = "fake"))]
+pub use fake_backend::FakeContextState;
+#[cfg(any(test, feature = "fake"))]
+pub use fake_backend::fake_proc_address;
+#[cfg(any(test, feature = "fake"))]
+pub use fake_backend::FakeMakeApiBackend;
+#[cfg(any(test, feature = "fake"))]
+pub use fake_backend::FakeApiBackend;
+#[cfg(any(test, feature = "fake"))]
+pub use fake_backend::FakeContextBackend;
+#[cfg(any(test, feature = "fake"))]
+pub use fake_backend::FakeSharingGroupBackend;
 
-//#[cfg(glfw)]
+#[cfg(feature = "egl")]
+mod egl_backend;
+#[cfg(feature = "egl")]
+pub use egl_backend::EglError;
+#[cfg(feature = "egl")]
+pub use egl_backend::EglDisplay;
+#[cfg(feature = "egl")]
+pub use egl_backend::EglMakeApiBackend;
+#[cfg(feature = "egl")]
+pub use egl_backend::EglApiBackend;
+#[cfg(feature = "egl")]
+pub use egl_backend::EglContextBackend;
+#[cfg(feature = "egl")]
+pub use egl_backend::EglSharingGroupBackend;
+
+#[cfg(feature = "glfw")]
 extern crate glfw;
+#[cfg(feature = "glfw")]
+mod glfw_backend;
+#[cfg(feature = "glfw")]
+pub use glfw_backend::glfw_window_hints;
+#[cfg(feature = "glfw")]
+pub use glfw_backend::set_glfw_window_hints;
+#[cfg(feature = "glfw")]
+pub use glfw_backend::create_glfw_window;
+#[cfg(feature = "glfw")]
+pub use glfw_backend::GlfwMakeApiBackend;
+#[cfg(feature = "glfw")]
+pub use glfw_backend::GlfwApiBackend;
+#[cfg(feature = "glfw")]
+pub use glfw_backend::GlfwContextBackend;
+
+#[cfg(feature = "log")]
+#[macro_use]
+extern crate log;
 
 #[cfg(test)]
-mod tests {
+mod fake_tests {
     use super::*;
-    use std::os::raw::c_void;
-    use std::mem::transmute;
+    use gl;
     use std::sync::Arc;
     use std::rc::Rc;
-    use std::io::{Write, stderr};
-    use glfw;
+    use std::cell::{Cell, RefCell};
+    use std::os::raw::c_void;
+    use std::ptr;
+    use std::panic;
+
+    thread_local! {
+        static DROP_ERRORS_: RefCell<Vec<&'static str>> = RefCell::new(Vec::new());
+    }
+
+    /// Error hook collecting the operations of the drop errors of the test thread.
+    fn drop_error_hook_(operation: &'static str, _: &Error) {
+        DROP_ERRORS_.with(|drop_errors| drop_errors.borrow_mut().push(operation));
+    }
+
+    /// Take the operations collected by drop_error_hook_.
+    fn take_drop_errors_() -> Vec<&'static str> {
+        DROP_ERRORS_.with(|drop_errors| drop_errors.borrow_mut().split_off(0))
+    }
+
+    fn make_api_() -> Arc<Api> {
+        Arc::new(ApiBuilder::new().error_check(ErrorCheck::Always).build(FakeMakeApiBackend).unwrap())
+    }
+
+    fn make_context_(api: &Arc<Api>, backend: FakeContextBackend) -> (Rc<Context>, Rc<RefCell<FakeContextState>>) {
+        let state = backend.state();
+        let context = unsafe { Rc::new(Context::new(api.clone(), Box::new(backend))) };
+        (context, state)
+    }
+
+    fn make_shared_context_(sharing_group: &Arc<SharingGroup>, backend: FakeContextBackend) -> (Rc<Context>, Rc<RefCell<FakeContextState>>) {
+        let state = backend.state();
+        let context = unsafe { Rc::new(Context::new_in_sharing_group(sharing_group.clone(), Box::new(backend))) };
+        (context, state)
+    }
+
+    #[test]
+    fn buffer_bind_is_cached() {
+        let api = make_api_();
+        let (context, state) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context).unwrap();
+        let buffer = CurrentArrayBuffer::create(current_context.clone()).unwrap();
+        buffer.set_data(&current_context, &[1u8, 2, 3]).unwrap();
+        buffer.set_data(&current_context, &[4u8, 5]).unwrap();
+        assert_eq!(state.borrow().call_count("glBindBuffer"), 1);
+        assert_eq!(state.borrow().objects.lock().unwrap().buffers[&buffer.gl_handle()].data, vec![4, 5]);
+        assert_eq!(current_context.state_cache().bound_array_buffer_gl_handle.get(), buffer.gl_handle());
+    }
+
+    #[test]
+    fn buffer_drop_deletes_and_unmarks() {
+        let api = make_api_();
+        let (context, state) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context).unwrap();
+        let buffer = CurrentElementArrayBuffer::create(current_context.clone()).unwrap();
+        buffer.bind(&current_context).unwrap();
+        drop(buffer);
+        assert!(state.borrow().objects.lock().unwrap().buffers.is_empty());
+        assert_eq!(current_context.state_cache().bound_element_array_buffer_gl_handle.get(), 0);
+    }
+
+    #[test]
+    fn context_switch_clears_state_cache() {
+        let api = make_api_();
+        let sharing_group_backend = FakeSharingGroupBackend::new();
+        let backend_1 = sharing_group_backend.create_context_backend();
+        let backend_2 = sharing_group_backend.create_context_backend();
+        let sharing_group = unsafe { SharingGroup::new(api.clone(), Box::new(sharing_group_backend)) };
+        let (context_1, state_1) = make_shared_context_(&sharing_group, backend_1);
+        let (context_2, state_2) = make_shared_context_(&sharing_group, backend_2);
+        let current_context = make_current(context_1).unwrap();
+        let buffer = CurrentArrayBuffer::create(current_context.clone()).unwrap();
+        buffer.bind(&current_context).unwrap();
+        let current_context_2 = make_current(context_2.clone()).unwrap();
+        assert!(Rc::ptr_eq(&current_context, &current_context_2));
+        assert!(context_2.backend().is_current());
+        assert_eq!(current_context.state_cache().bound_array_buffer_gl_handle.get(), 0);
+        buffer.bind(&current_context).unwrap();
+        assert_eq!(state_1.borrow().call_count("glBindBuffer"), 1);
+        assert_eq!(state_2.borrow().call_count("glBindBuffer"), 1);
+        assert_eq!(state_2.borrow().bound_buffers[&gl::ARRAY_BUFFER], buffer.gl_handle());
+    }
+
+    #[test]
+    fn gl_error_names_call() {
+        let api = make_api_();
+        let (context, _) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context).unwrap();
+        unsafe {
+            current_context.gl().BindBuffer(gl::ARRAY_BUFFER, 1234);
+        }
+        match current_context.check_errors("glBindBuffer") {
+            Err(Error::Gl { call, errors }) => {
+                assert_eq!(call, "glBindBuffer");
+                assert_eq!(errors, vec![GlError::InvalidOperation]);
+            },
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert!(current_context.check_errors("glBindBuffer").is_ok());
+    }
+
+    #[test]
+    fn error_check_policy_controls_get_error_calls() {
+        let api = make_api_();
+        let (context, state) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context).unwrap();
+        let get_error_calls = |check: &Fn() -> Result<(), Error>| {
+            state.borrow_mut().errors.extend_from_slice(&[gl::INVALID_ENUM, gl::INVALID_VALUE]);
+            let count = state.borrow().call_count("glGetError");
+            let res = check();
+            (state.borrow().call_count("glGetError") - count, res.is_err())
+        };
+        // Always drains all errors and the final GL_NO_ERROR, after calls and frames.
+        assert_eq!(get_error_calls(&|| current_context.check_errors("glBindBuffer")), (3, true));
+        assert_eq!(get_error_calls(&|| current_context.check_frame_errors()), (3, true));
+        api.set_error_check(ErrorCheck::Never);
+        assert_eq!(api.error_check(), ErrorCheck::Never);
+        assert_eq!(get_error_calls(&|| current_context.check_errors("glBindBuffer")), (0, false));
+        assert_eq!(get_error_calls(&|| current_context.check_frame_errors()), (0, false));
+        state.borrow_mut().errors.clear();
+        api.set_error_check(ErrorCheck::PerFrame);
+        assert_eq!(get_error_calls(&|| current_context.check_errors("glBindBuffer")), (0, false));
+        assert_eq!(get_error_calls(&|| current_context.check_frame_errors()), (5, true));
+        api.set_error_check(ErrorCheck::DebugOnly);
+        let expected = if cfg!(debug_assertions) { (3, true) } else { (0, false) };
+        assert_eq!(get_error_calls(&|| current_context.check_errors("glBindBuffer")), expected);
+        state.borrow_mut().errors.clear();
+        api.set_error_check(ErrorCheck::Always);
+        assert_eq!(get_error_calls(&|| current_context.check_errors("glBindBuffer")), (3, true));
+    }
+
+    #[test]
+    fn debug_callback_receives_messages() {
+        let api = make_api_();
+        let backend = FakeContextBackend::new();
+        backend.state().borrow_mut().set_extensions("GL_KHR_debug");
+        let (context, state) = make_context_(&api, backend);
+        let current_context = make_current(context.clone()).unwrap();
+        let messages = Rc::new(RefCell::new(Vec::new()));
+        {
+            let messages = messages.clone();
+            current_context.set_debug_callback(move |message: &DebugMessage| {
+                messages.borrow_mut().push((message.type_, message.severity, message.message.to_string()));
+            }).unwrap();
+        }
+        unsafe {
+            current_context.gl().BindBuffer(gl::ARRAY_BUFFER, 1234);
+        }
+        assert!(current_context.check_errors("glBindBuffer").is_err());
+        assert_eq!(*messages.borrow(), vec![(DebugType::Error, DebugSeverity::High, format!("error {:#x} raised", gl::INVALID_OPERATION))]);
+        current_context.debug_message_control(None, None, None, &[], false).unwrap();
+        assert!(!state.borrow().debug_messages_enabled);
+        unsafe {
+            current_context.gl().BindBuffer(gl::ARRAY_BUFFER, 1234);
+        }
+        assert!(current_context.check_errors("glBindBuffer").is_err());
+        assert_eq!(messages.borrow().len(), 1);
+        current_context.debug_message_control(None, None, None, &[], true).unwrap();
+
+        // A panic of the callback must not unwind into opengl.
+        current_context.set_debug_callback(|_: &DebugMessage| panic!("debug callback panicked")).unwrap();
+        assert_eq!(Rc::strong_count(&messages), 1);
+        unsafe {
+            current_context.gl().BindBuffer(gl::ARRAY_BUFFER, 1234);
+        }
+        assert!(current_context.check_errors("glBindBuffer").is_err());
+
+        current_context.clear_debug_callback().unwrap();
+        assert!(!state.borrow().enabled.contains(&gl::DEBUG_OUTPUT));
+        assert!(state.borrow().debug_callback.unwrap().1.is_null());
+    }
+
+    #[test]
+    fn debug_callback_is_set_again_after_clear() {
+        let api = make_api_();
+        let backend = FakeContextBackend::new();
+        backend.state().borrow_mut().set_extensions("GL_KHR_debug");
+        let (context, state) = make_context_(&api, backend);
+        let current_context = make_current(context.clone()).unwrap();
+        let message_count = Rc::new(Cell::new(0));
+        let set_callback = || {
+            let message_count = message_count.clone();
+            current_context.set_debug_callback(move |_: &DebugMessage| message_count.set(message_count.get() + 1)).unwrap();
+        };
+        let raise_error = || {
+            unsafe {
+                current_context.gl().BindBuffer(gl::ARRAY_BUFFER, 1234);
+            }
+            assert!(current_context.check_errors("glBindBuffer").is_err());
+        };
+        set_callback();
+        raise_error();
+        assert_eq!(message_count.get(), 1);
+        current_context.clear_debug_callback().unwrap();
+        assert!(!state.borrow().enabled.contains(&gl::DEBUG_OUTPUT_SYNCHRONOUS));
+        raise_error();
+        assert_eq!(message_count.get(), 1);
+        set_callback();
+        raise_error();
+        assert_eq!(message_count.get(), 2);
+    }
+
+    #[test]
+    fn debug_callback_is_removed_when_context_is_dropped() {
+        let api = make_api_();
+        let backend = FakeContextBackend::new();
+        backend.state().borrow_mut().set_extensions("GL_KHR_debug");
+        let (context, state) = make_context_(&api, backend);
+        let (other_context, other_state) = make_context_(&api, FakeContextBackend::new());
+        let counter = Rc::new(());
+        {
+            let current_context = make_current(context.clone()).unwrap();
+            let counter = counter.clone();
+            current_context.set_debug_callback(move |_: &DebugMessage| { let _ = &counter; }).unwrap();
+        }
+        assert_eq!(Rc::strong_count(&counter), 2);
+        // The context is not current when it is dropped, so it is made current to remove the
+        // callback, and the current context of the thread is restored afterwards.
+        let current_context = make_current(other_context.clone()).unwrap();
+        drop(context);
+        assert_eq!(Rc::strong_count(&counter), 1);
+        assert!(state.borrow().debug_callback.unwrap().1.is_null());
+        assert!(other_context.backend().is_current());
+        assert!(other_state.borrow().debug_callback.is_none());
+        drop(current_context);
+    }
+
+    #[test]
+    fn object_label_is_set() {
+        let api = make_api_();
+        let backend = FakeContextBackend::new();
+        backend.state().borrow_mut().set_extensions("GL_KHR_debug");
+        let (context, state) = make_context_(&api, backend);
+        let current_context = make_current(context).unwrap();
+        let mut program = CurrentProgram::create(current_context.clone()).unwrap();
+        program.set_label("sprites").unwrap();
+        assert_eq!(program.label(), Some("sprites"));
+        assert_eq!(state.borrow().objects.lock().unwrap().labels[&(gl::PROGRAM, program.gl_handle())], "sprites");
+    }
+
+    #[test]
+    fn errors_carry_object_label() {
+        let api = make_api_();
+        let (context, state) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context).unwrap();
+        // The label is kept without KHR_debug, where glObjectLabel is not called.
+        let mut program = CurrentProgram::create(current_context.clone()).unwrap();
+        program.set_label("sprites").unwrap();
+        assert_eq!(state.borrow().call_count("glObjectLabel"), 0);
+        let error = program.link().err().unwrap();
+        match error {
+            Error::Object { kind, gl_handle, ref label, .. } => {
+                assert_eq!(kind, ObjectKind::Program);
+                assert_eq!(gl_handle, program.gl_handle());
+                assert_eq!(label.as_ref().map(|label| &**label), Some("sprites"));
+            },
+            ref error => panic!("unexpected error {:?}", error),
+        }
+        assert_eq!(error.to_string(), format!("program {} \"sprites\": {}", program.gl_handle(), error.root()));
+    }
 
-    unsafe impl ApiBackend for glfw::Glfw {
-        unsafe fn clear_current_context(&self) -> Result<(), Error> {
-            self.make_context_current(None);
-            Ok(())
+    #[test]
+    fn debug_groups_are_nested_and_popped_on_drop() {
+        let api = make_api_();
+        api.set_error_hook(ErrorHook::Custom(drop_error_hook_));
+        let backend = FakeContextBackend::new();
+        backend.state().borrow_mut().set_extensions("GL_KHR_debug");
+        let (context, state) = make_context_(&api, backend);
+        let current_context = make_current(context).unwrap();
+        {
+            let _outer = current_context.debug_group("frame").unwrap();
+            {
+                let _inner = current_context.debug_group("shadows").unwrap();
+                assert_eq!(state.borrow().debug_groups, vec!["frame".to_string(), "shadows".to_string()]);
+            }
+            assert_eq!(state.borrow().debug_groups, vec!["frame".to_string()]);
         }
+        assert!(state.borrow().debug_groups.is_empty());
+        assert_eq!(state.borrow().call_count("glPopDebugGroup"), 2);
+        assert!(take_drop_errors_().is_empty());
+
+        // A group popped behind the back of the guard lets the pop on drop fail.
+        let group = current_context.debug_group("frame").unwrap();
+        unsafe { current_context.gl().PopDebugGroup() };
+        drop(group);
+        assert_eq!(take_drop_errors_(), vec!["glPopDebugGroup"]);
     }
-    unsafe impl<'w> MakeApiBackend for &'w glfw::Window {
+
+    #[test]
+    fn debug_group_without_khr_debug_does_nothing() {
+        let api = make_api_();
+        let (context, state) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context).unwrap();
+        drop(current_context.debug_group("frame").unwrap());
+        assert_eq!(state.borrow().call_count("glPushDebugGroup"), 0);
+        assert_eq!(state.borrow().call_count("glPopDebugGroup"), 0);
+        assert!(!current_context.has_debug_output());
+        match current_context.set_debug_callback(|_: &DebugMessage| ()) {
+            Err(Error::Unsupported("GL_KHR_debug")) => (),
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn shader_compile_error_maps_lines() {
+        let api = make_api_();
+        let (context, _) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context).unwrap();
+        let error = CurrentVertexShader::create_from_src(current_context, "void main() {\n#error broken\n}\n").err().unwrap();
+        match *error.root() {
+            Error::ShaderCompile(ref error) => {
+                assert_eq!(error.stage, ShaderStage::Vertex);
+                assert_eq!(error.diagnostics.len(), 1);
+                assert_eq!(error.diagnostics[0].line, Some(2));
+                assert_eq!(error.diagnostics[0].source_line, Some("#error broken".to_string()));
+            },
+            ref error => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn program_link_error() {
+        let api = make_api_();
+        let (context, _) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context).unwrap();
+        let vertex_shader = CurrentVertexShader::create_from_src(current_context.clone(), "void main() {}").unwrap();
+        let program = CurrentProgram::create(current_context.clone()).unwrap();
+        program.attach_shader(&vertex_shader).unwrap();
+        match *program.link().err().unwrap().root() {
+            Error::ProgramLink(ref info_log) => assert!(info_log.contains("fragment shader")),
+            ref error => panic!("unexpected error {:?}", error),
+        }
+        assert!(program.validate().is_err());
+        let program = CurrentProgram::create_from_vertex_and_fragment_shader_src(current_context, "void main() {}", "void main() {}").unwrap();
+        assert!(program.validate().is_ok());
+    }
+
+    #[test]
+    fn split_buffer_stays_live() {
+        let api = make_api_();
+        let (context, _) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context).unwrap();
+        let mut buffer = CurrentArrayBuffer::create(current_context.clone()).unwrap();
+        buffer.set_label("vertices").unwrap();
+        let shader = CurrentVertexShader::create(current_context.clone()).unwrap();
+        drop(shader);
+        let (gl_handle, _) = unsafe { buffer.split() };
+        let live_objects = api.live_objects();
+        assert_eq!(live_objects.len(), 1);
+        assert_eq!(live_objects[0].kind, ObjectKind::Buffer);
+        assert_eq!(live_objects[0].gl_handle, gl_handle);
+        assert_eq!(live_objects[0].label, Some("vertices".to_string()));
+        drop(unsafe { CurrentArrayBuffer::from_gl_handle(gl_handle, current_context) });
+        assert!(api.live_objects().is_empty());
+    }
+
+    #[test]
+    fn api_info_is_queried_on_first_make_current() {
+        let api = make_api_();
+        assert!(api.info().is_none());
+        let backend = FakeContextBackend::new();
+        backend.state().borrow_mut().set_extensions("GL_KHR_debug GL_ARB_robustness");
+        let (context, _) = make_context_(&api, backend);
+        let current_context = make_current(context).unwrap();
+        {
+            let info = api.info().unwrap();
+            assert_eq!(info.version, Version::new(2, 0));
+            assert_eq!(info.glsl_version, Some(Version::new(1, 10)));
+            assert_eq!(info.renderer, "scgl fake");
+            assert!(info.has_extension("GL_ARB_robustness"));
+            assert_eq!(info.limits.max_texture_size, 2048);
+            assert_eq!(info.limits.max_uniform_block_size, None);
+        }
+        assert!(current_context.has_extension("GL_KHR_debug"));
+        assert!(current_context.check_errors("glGetIntegerv").is_ok());
+        let (context_2, state_2) = make_context_(&api, FakeContextBackend::new());
+        make_current(context_2).unwrap();
+        assert_eq!(state_2.borrow().call_count("glGetString"), 0);
+        assert_eq!(Version::parse("OpenGL ES GLSL ES 3.00"), Some(Version::new(3, 0)));
+        assert_eq!(Version::parse("4.5.0 NVIDIA 390.48"), Some(Version::new(4, 5)));
+    }
+
+    /// Fake api backend which does not resolve the procedure `0`.
+    struct WithoutProc_(&'static str);
+    unsafe impl MakeApiBackend for WithoutProc_ {
         unsafe fn get_proc_address(&mut self, name: &str) -> *const c_void {
-            let res = unsafe { transmute(self.get_proc_address(name)) };
-            let addr: usize = transmute(res);
-            writeln!(&mut stderr(), "get_proc_address {}: {}", name, addr);
-            res
+            if name == self.0 { ptr::null() } else { fake_proc_address(name) }
         }
         unsafe fn into_backend(self) -> Box<ApiBackend> {
-            Box::new(glfw::Glfw)
+            Box::new(FakeApiBackend)
+        }
+    }
+
+    #[test]
+    fn missing_function_is_reported() {
+        let api = Arc::new(ApiBuilder::new().build(WithoutProc_("glBufferData")).unwrap());
+        assert!(api.load_report().missing_core.iter().any(|name| name == "glBufferData"));
+        assert!(!api.load_report().missing_core.iter().any(|name| name == "glGenBuffers"));
+        let (context, _) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context).unwrap();
+        let buffer = CurrentArrayBuffer::create(current_context.clone()).unwrap();
+        match buffer.set_data(&current_context, &[1u8]) {
+            Err(Error::MissingFunctions(names)) => assert_eq!(names, vec!["glBufferData".to_string()]),
+            res => panic!("unexpected result {:?}", res),
+        }
+        drop(buffer);
+        drop(current_context);
+        drop(api);
+        match ApiBuilder::new().require_core_functions(true).build(WithoutProc_("glBufferData")) {
+            Err(Error::MissingFunctions(names)) => assert!(names.iter().any(|name| name == "glBufferData")),
+            res => panic!("unexpected result {:?}", res.map(|_| ())),
         }
     }
-    struct GlfwContextBackend(pub Rc<glfw::Window>);
-    unsafe impl ContextBackend for GlfwContextBackend {
-        fn is_current(&self) -> bool {
-            use glfw::Context;
-            self.0.is_current();
+
+    #[test]
+    fn apis_are_independent() {
+        let api_1 = make_api_();
+        let api_2 = make_api_();
+        assert!(api_1.id() != api_2.id());
+        let (context_1, _) = make_context_(&api_1, FakeContextBackend::new());
+        let (context_2, _) = make_context_(&api_2, FakeContextBackend::new());
+        let current_context = make_current(context_1).unwrap();
+        let buffer = CurrentArrayBuffer::create(current_context.clone()).unwrap();
+        match make_current(context_2.clone()) {
+            Err(Error::ApiMismatch) => (),
+            res => panic!("unexpected result {:?}", res.map(|_| ())),
+        }
+        let async_buffer = unsafe { buffer.to_async().unwrap() };
+        drop(current_context);
+        let current_context_2 = make_current(context_2).unwrap();
+        assert!(Arc::ptr_eq(current_context_2.api(), &api_2));
+        match async_buffer.to_current(current_context_2).map(|_| ()).err().unwrap().1.root() {
+            &Error::ApiMismatch => (),
+            error => panic!("unexpected error {:?}", error),
+        }
+        // The leaked buffer was reported, when the async buffer dropped the last reference to the
+        // sharing group.
+        assert!(api_1.live_objects().is_empty());
+    }
+
+    #[test]
+    fn versioned_context_checks_version() {
+        // The kind defaults to the kind of the bindings.
+        let api = make_api_();
+        let expected_kind = if cfg!(any(feature = "gles2", feature = "gles3")) { ApiKind::Gles } else { ApiKind::Gl };
+        assert_eq!(api.kind(), expected_kind);
+        let (context, _) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context).unwrap();
+        if expected_kind == ApiKind::Gl {
+            assert!(VersionedContext::<Gl20>::new(current_context.clone()).is_ok());
+            match VersionedContext::<Gl33>::new(current_context.clone()).map(|_| ()) {
+                Err(Error::Unsupported(name)) => assert_eq!(name, "OpenGL 3.3"),
+                res => panic!("unexpected result {:?}", res),
+            }
+            assert!(VersionedContext::<Gles20>::new(current_context).is_err());
+        } else {
+            assert!(VersionedContext::<Gles20>::new(current_context.clone()).is_ok());
+            match VersionedContext::<Gles30>::new(current_context.clone()).map(|_| ()) {
+                Err(Error::Unsupported(name)) => assert_eq!(name, "OpenGL ES 3.0"),
+                res => panic!("unexpected result {:?}", res),
+            }
+            assert!(VersionedContext::<Gl20>::new(current_context).is_err());
+        }
+    }
+
+    /// Create an api of the kind of `V`, whose contexts report the version of `V`.
+    #[cfg(any(feature = "gl33", feature = "gl45", feature = "gles3"))]
+    fn make_versioned_api_<V: ApiVersion, MAB: MakeApiBackend>(mab: MAB) -> Arc<Api> {
+        Arc::new(ApiBuilder::new().error_check(ErrorCheck::Always).kind(V::kind()).build(mab).unwrap())
+    }
+    #[cfg(any(feature = "gl33", feature = "gl45", feature = "gles3"))]
+    fn make_versioned_context_<V: ApiVersion>(api: &Arc<Api>) -> (Rc<Context>, Rc<RefCell<FakeContextState>>) {
+        let backend = FakeContextBackend::new();
+        backend.state().borrow_mut().set_version(&format!("{} scgl fake", V::version()));
+        make_context_(api, backend)
+    }
+
+    #[cfg(all(any(feature = "gl33", feature = "gl45"), not(feature = "gles3")))]
+    type VertexArrayVersion_ = Gl33;
+    #[cfg(feature = "gles3")]
+    type VertexArrayVersion_ = Gles30;
+
+    #[cfg(any(feature = "gl33", feature = "gl45", feature = "gles3"))]
+    #[test]
+    fn vertex_array_is_created_with_version() {
+        let api = make_versioned_api_::<VertexArrayVersion_, _>(FakeMakeApiBackend);
+        let (context_1, state_1) = make_versioned_context_::<VertexArrayVersion_>(&api);
+        let (context_2, _) = make_versioned_context_::<VertexArrayVersion_>(&api);
+        let current_context = make_current(context_1.clone()).unwrap();
+        let versioned_context = VersionedContext::<VertexArrayVersion_>::new(current_context.clone()).unwrap();
+        let vertex_array = CurrentVertexArray::create(&versioned_context).unwrap();
+        vertex_array.bind().unwrap();
+        assert_eq!(state_1.borrow().bound_vertex_array, vertex_array.gl_handle());
+        // The version was checked with the first context.
+        make_current(context_2).unwrap();
+        match CurrentVertexArray::create(&versioned_context).err().unwrap() {
+            Error::WrongContext => (),
+            error => panic!("unexpected error {:?}", error),
+        }
+        make_current(context_1).unwrap();
+        assert!(CurrentVertexArray::create(&versioned_context).is_ok());
+        drop(vertex_array);
+        drop(versioned_context);
+        drop(current_context);
+
+        let api = make_versioned_api_::<VertexArrayVersion_, _>(WithoutProc_("glGenVertexArrays"));
+        let (context, _) = make_versioned_context_::<VertexArrayVersion_>(&api);
+        let versioned_context = VersionedContext::<VertexArrayVersion_>::new(make_current(context).unwrap()).unwrap();
+        match CurrentVertexArray::create(&versioned_context).err().unwrap() {
+            Error::MissingFunctions(names) => assert_eq!(names, vec!["glGenVertexArrays".to_string()]),
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    #[cfg(any(feature = "gl33", feature = "gl45", feature = "gles3"))]
+    #[test]
+    fn vertex_array_is_created_checked_with_version_or_extension() {
+        let extension = if cfg!(feature = "gles3") { "GL_OES_vertex_array_object" } else { "GL_ARB_vertex_array_object" };
+        // The procedures are loaded, but neither the version nor the extension is supported.
+        let api = make_api_();
+        let (context, _) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context).unwrap();
+        match CurrentVertexArray::create_checked(current_context.clone()).err().unwrap() {
+            Error::Unsupported(name) => assert_eq!(name, extension),
+            error => panic!("unexpected error {:?}", error),
+        }
+        drop(current_context);
+
+        let api = make_api_();
+        let backend = FakeContextBackend::new();
+        backend.state().borrow_mut().set_extensions(extension);
+        let (context, _) = make_context_(&api, backend);
+        let current_context = make_current(context).unwrap();
+        assert!(CurrentVertexArray::create_checked(current_context.clone()).is_ok());
+    }
+
+    #[cfg(any(feature = "gl33", feature = "gl45", feature = "gles3"))]
+    #[test]
+    fn vertex_arrays_are_registered_per_context() {
+        let api = make_api_();
+        let sharing_group_backend = FakeSharingGroupBackend::new();
+        let backend_1 = sharing_group_backend.create_context_backend();
+        let backend_2 = sharing_group_backend.create_context_backend();
+        let sharing_group = unsafe { SharingGroup::new(api.clone(), Box::new(sharing_group_backend)) };
+        backend_1.state().borrow_mut().set_version("3.0 scgl fake");
+        let (context_1, _) = make_shared_context_(&sharing_group, backend_1);
+        let (context_2, _) = make_shared_context_(&sharing_group, backend_2);
+        let current_context = make_current(context_1.clone()).unwrap();
+        let mut vertex_array_1 = CurrentVertexArray::create_checked(current_context.clone()).unwrap();
+        vertex_array_1.set_label("first").unwrap();
+        make_current(context_2.clone()).unwrap();
+        let vertex_array_2 = CurrentVertexArray::create_checked(current_context.clone()).unwrap();
+        // Both contexts hand out the same name, which is registered once per context.
+        assert_eq!(vertex_array_1.gl_handle(), vertex_array_2.gl_handle());
+        let live_objects = api.live_objects();
+        assert_eq!(live_objects.len(), 2);
+        assert_eq!(live_objects[0].context, Some(context_1.id()));
+        assert_eq!(live_objects[0].label, Some("first".to_string()));
+        assert_eq!(live_objects[1].context, Some(context_2.id()));
+        assert_eq!(live_objects[1].label, None);
+        drop(vertex_array_2);
+        let live_objects = api.live_objects();
+        assert_eq!(live_objects.len(), 1);
+        assert_eq!(live_objects[0].context, Some(context_1.id()));
+        make_current(context_1).unwrap();
+        drop(vertex_array_1);
+        assert!(api.live_objects().is_empty());
+    }
+
+    #[cfg(feature = "gl45")]
+    #[test]
+    fn buffer_storage_is_set_with_version() {
+        let api = make_versioned_api_::<Gl45, _>(FakeMakeApiBackend);
+        let (context_1, state_1) = make_versioned_context_::<Gl45>(&api);
+        let (context_2, _) = make_versioned_context_::<Gl45>(&api);
+        let current_context = make_current(context_1.clone()).unwrap();
+        let versioned_context = VersionedContext::<Gl45>::new(current_context.clone()).unwrap();
+        let buffer = CurrentArrayBuffer::create(current_context.clone()).unwrap();
+        buffer.set_storage(&versioned_context, &[1u8, 2, 3], gl::DYNAMIC_STORAGE_BIT).unwrap();
+        {
+            let state_1 = state_1.borrow();
+            let objects = state_1.objects.lock().unwrap();
+            assert_eq!(objects.buffers[&buffer.gl_handle()].data, vec![1, 2, 3]);
+            assert_eq!(objects.buffers[&buffer.gl_handle()].storage_flags, Some(gl::DYNAMIC_STORAGE_BIT));
+        }
+        // The storage is immutable.
+        assert!(buffer.set_data(&current_context, &[4u8]).is_err());
+        make_current(context_2).unwrap();
+        match *buffer.set_storage(&versioned_context, &[1u8], 0).err().unwrap().root() {
+            Error::WrongContext => (),
+            ref error => panic!("unexpected error {:?}", error),
+        }
+        make_current(context_1).unwrap();
+        drop(buffer);
+        drop(versioned_context);
+        drop(current_context);
+
+        let api = make_versioned_api_::<Gl45, _>(WithoutProc_("glBufferStorage"));
+        let (context, _) = make_versioned_context_::<Gl45>(&api);
+        let current_context = make_current(context).unwrap();
+        let versioned_context = VersionedContext::<Gl45>::new(current_context.clone()).unwrap();
+        let buffer = CurrentArrayBuffer::create(current_context).unwrap();
+        match *buffer.set_storage(&versioned_context, &[1u8], 0).err().unwrap().root() {
+            Error::MissingFunctions(ref names) => assert_eq!(*names, vec!["glBufferStorage".to_string()]),
+            ref error => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn objects_are_bound_to_sharing_group() {
+        let api = make_api_();
+        let (context_1, _) = make_context_(&api, FakeContextBackend::new());
+        let (context_2, _) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context_1.clone()).unwrap();
+        let buffer = CurrentArrayBuffer::create(current_context.clone()).unwrap();
+        let sharing_group_id = context_1.sharing_group().id();
+        assert!(sharing_group_id != context_2.sharing_group().id());
+        make_current(context_2).unwrap();
+        match *buffer.bind(&current_context).err().unwrap().root() {
+            Error::WrongSharingGroup => (),
+            ref error => panic!("unexpected error {:?}", error),
+        }
+        assert!(SharingGroup::create_context(context_1.sharing_group()).is_err());
+        make_current(context_1).unwrap();
+        drop(buffer);
+        assert!(api.live_objects().is_empty());
+
+        let sharing_group = unsafe { SharingGroup::new(api.clone(), Box::new(FakeSharingGroupBackend::new())) };
+        let context_3 = SharingGroup::create_context(&sharing_group).unwrap();
+        let context_4 = SharingGroup::create_context(&sharing_group).unwrap();
+        make_current(context_3).unwrap();
+        let buffer = CurrentArrayBuffer::create(current_context.clone()).unwrap();
+        make_current(context_4).unwrap();
+        buffer.bind(&current_context).unwrap();
+        drop(current_context);
+        let leaked = unsafe { buffer.split() }.0;
+        assert_eq!(api.live_objects()[0].gl_handle, leaked);
+        // The leak is reported to the error hook.
+        api.set_error_hook(ErrorHook::Custom(drop_error_hook_));
+        drop(sharing_group);
+        assert!(api.live_objects().is_empty());
+        assert_eq!(take_drop_errors_(), vec!["dropping the sharing group"]);
+    }
+
+    #[test]
+    fn sharing_group_current_reuses_context() {
+        let api = make_api_();
+        let sharing_group_backend = FakeSharingGroupBackend::new();
+        let backend = sharing_group_backend.create_context_backend();
+        let sharing_group = unsafe { SharingGroup::new(api.clone(), Box::new(sharing_group_backend)) };
+        let (context, _) = make_shared_context_(&sharing_group, backend);
+        let (other_context, _) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context.clone()).unwrap();
+        make_sharing_group_current(&sharing_group).unwrap();
+        assert!(Rc::ptr_eq(current_context.context(), &context));
+        make_current(other_context.clone()).unwrap();
+        make_sharing_group_current(&sharing_group).unwrap();
+        assert!(!Rc::ptr_eq(current_context.context(), &context));
+        assert!(current_context.sharing_group().id() == sharing_group.id());
+        assert!(current_context.context().backend().is_current());
+        match make_sharing_group_current(other_context.sharing_group()).map(|_| ()) {
+            Err(Error::Unsupported(_)) => (),
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        // The hidden context is reused instead of created again.
+        let hidden_context = current_context.context().clone();
+        make_current(other_context.clone()).unwrap();
+        make_sharing_group_current(&sharing_group).unwrap();
+        assert!(Rc::ptr_eq(current_context.context(), &hidden_context));
+        make_current(other_context).unwrap();
+        release_hidden_contexts();
+        assert_eq!(Rc::strong_count(&hidden_context), 1);
+        drop(hidden_context);
+        drop(context);
+        assert_eq!(Arc::strong_count(&sharing_group), 1);
+    }
+
+    #[test]
+    fn unused_hidden_context_is_dropped() {
+        let api = make_api_();
+        let (other_context, _) = make_context_(&api, FakeContextBackend::new());
+        let sharing_group = unsafe { SharingGroup::new(api.clone(), Box::new(FakeSharingGroupBackend::new())) };
+        let weak_sharing_group = Arc::downgrade(&sharing_group);
+        let current_context = make_sharing_group_current(&sharing_group).unwrap();
+        make_current(other_context.clone()).unwrap();
+        // Only the hidden context keeps the sharing group alive now, which is checked by the next
+        // call of make_sharing_group_current.
+        drop(sharing_group);
+        assert!(weak_sharing_group.upgrade().is_some());
+        let sharing_group_2 = unsafe { SharingGroup::new(api.clone(), Box::new(FakeSharingGroupBackend::new())) };
+        make_sharing_group_current(&sharing_group_2).unwrap();
+        assert!(weak_sharing_group.upgrade().is_none());
+        drop(current_context);
+        release_hidden_contexts();
+    }
+
+    #[test]
+    fn buffer_is_created_in_worker_thread() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SharingGroup>();
+        assert_send_sync::<AsyncBuffer<ArrayBufferTarget>>();
+
+        let api = make_api_();
+        let sharing_group_backend = FakeSharingGroupBackend::new();
+        let backend = sharing_group_backend.create_context_backend();
+        let state = backend.state();
+        let sharing_group = unsafe { SharingGroup::new(api.clone(), Box::new(sharing_group_backend)) };
+        let worker_sharing_group = sharing_group.clone();
+        let async_buffer = std::thread::spawn(move || {
+            let current_context = make_sharing_group_current(&worker_sharing_group).unwrap();
+            let buffer = CurrentArrayBuffer::create(current_context.clone()).unwrap();
+            buffer.set_data(&current_context, &[7u8, 8, 9]).unwrap();
+            buffer.into_async().unwrap()
+        }).join().unwrap();
+        // A failed conversion returns the async buffer, so it can be retried.
+        let (other_context, _) = make_context_(&api, FakeContextBackend::new());
+        let other_current_context = make_current(other_context).unwrap();
+        let (async_buffer, error) = async_buffer.to_current(other_current_context).err().unwrap();
+        match *error.root() {
+            Error::WrongSharingGroup => (),
+            ref error => panic!("unexpected error {:?}", error),
         }
-        unsafe fn make_current(&self) -> Result<(), Error> {
-            use glfw::Context;
-            self.0.make_current();
-            Ok(())
+        let (context, _) = make_shared_context_(&sharing_group, backend);
+        let current_context = make_current(context).unwrap();
+        let buffer = async_buffer.to_current(current_context.clone()).unwrap();
+        assert_eq!(state.borrow().objects.lock().unwrap().buffers[&buffer.gl_handle()].data, vec![7, 8, 9]);
+        drop(buffer);
+        assert!(api.live_objects().is_empty());
+    }
+
+    #[test]
+    fn external_context_changes() {
+        let api = make_api_();
+        let (context, state) = make_context_(&api, FakeContextBackend::new());
+        let host_backend = FakeContextBackend::new();
+        let current_context = make_current(context.clone()).unwrap();
+        let buffer = CurrentArrayBuffer::create(current_context.clone()).unwrap();
+        buffer.bind(&current_context).unwrap();
+
+        api.set_external_changes(ExternalChanges::Trust);
+        unsafe { host_backend.make_current().unwrap() };
+        make_current(context.clone()).unwrap();
+        assert!(!context.backend().is_current());
+        assert_eq!(current_context.state_cache().bound_array_buffer_gl_handle.get(), buffer.gl_handle());
+
+        api.set_external_changes(ExternalChanges::CheckCurrent);
+        make_current(context.clone()).unwrap();
+        assert!(context.backend().is_current());
+        assert_eq!(current_context.state_cache().bound_array_buffer_gl_handle.get(), 0);
+
+        api.set_external_changes(ExternalChanges::Resync);
+        let get_integerv_count = state.borrow().call_count("glGetIntegerv");
+        make_current(context.clone()).unwrap();
+        assert!(state.borrow().call_count("glGetIntegerv") > get_integerv_count);
+        assert_eq!(current_context.state_cache().bound_array_buffer_gl_handle.get(), buffer.gl_handle());
+    }
+
+    #[test]
+    fn with_context_restores_previous_context() {
+        let api = make_api_();
+        let (main_context, _) = make_context_(&api, FakeContextBackend::new());
+        let (thumbnail_context, _) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(main_context.clone()).unwrap();
+        let buffer = CurrentArrayBuffer::create(current_context.clone()).unwrap();
+        buffer.bind(&current_context).unwrap();
+        let res = with_context(&thumbnail_context, |current_context| {
+            assert!(Rc::ptr_eq(current_context.context(), &thumbnail_context));
+            assert!(thumbnail_context.backend().is_current());
+            assert_eq!(current_context.state_cache().bound_array_buffer_gl_handle.get(), 0);
+            42
+        }).unwrap();
+        assert_eq!(res, 42);
+        assert!(Rc::ptr_eq(current_context.context(), &main_context));
+        assert!(main_context.backend().is_current());
+        assert_eq!(current_context.state_cache().bound_array_buffer_gl_handle.get(), buffer.gl_handle());
+    }
+
+    #[test]
+    fn with_context_reports_restore_error() {
+        let api = make_api_();
+        api.set_error_hook(ErrorHook::Custom(drop_error_hook_));
+        let (main_context, main_state) = make_context_(&api, FakeContextBackend::new());
+        let (thumbnail_context, _) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(main_context.clone()).unwrap();
+        let res = with_context(&thumbnail_context, |_| {
+            main_state.borrow_mut().make_current_fails = true;
+            42
+        }).unwrap();
+        // The result is returned although the previous context is not restored.
+        assert_eq!(res, 42);
+        assert_eq!(take_drop_errors_(), vec!["restoring the previous context"]);
+        main_state.borrow_mut().make_current_fails = false;
+        drop(current_context);
+    }
+
+    #[test]
+    fn with_context_restores_previous_context_on_panic() {
+        let api = make_api_();
+        let (main_context, _) = make_context_(&api, FakeContextBackend::new());
+        let (thumbnail_context, _) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(main_context.clone()).unwrap();
+        let res = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            with_context(&thumbnail_context, |_| panic!("thumbnail failed"))
+        }));
+        assert!(res.is_err());
+        assert!(Rc::ptr_eq(current_context.context(), &main_context));
+        assert!(main_context.backend().is_current());
+    }
+
+    #[test]
+    fn release_and_drop_errors() {
+        let api = make_api_();
+        api.set_error_hook(ErrorHook::Custom(drop_error_hook_));
+        let (context, _) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context.clone()).unwrap();
+        let buffer = CurrentArrayBuffer::create(current_context.clone()).unwrap();
+        match CurrentContext::release(current_context.clone()) {
+            Err(Error::ContextInUse) => (),
+            res => panic!("unexpected result {:?}", res),
         }
+        // Leave an error flag, which is drained by the check after glDeleteBuffers.
+        unsafe { current_context.gl().GetIntegerv(0xFFFF, ptr::null_mut()) };
+        drop(buffer);
+        assert_eq!(take_drop_errors_(), vec!["glDeleteBuffers"]);
+        CurrentContext::release(current_context).unwrap();
+        assert!(!context.backend().is_current());
+    }
+
+    #[test]
+    fn reset_marks_sharing_group_lost() {
+        let api = make_api_();
+        let reset_statuses = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let callback_reset_statuses = reset_statuses.clone();
+        api.set_reset_callback(move |_, reset_status| callback_reset_statuses.lock().unwrap().push(reset_status));
+        let (context, state) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context).unwrap();
+        let buffer = CurrentArrayBuffer::create(current_context.clone()).unwrap();
+        assert_eq!(current_context.reset_status().unwrap(), ResetStatus::NoError);
+        assert!(!current_context.sharing_group().is_lost());
+
+        state.borrow_mut().reset_status = gl::GUILTY_CONTEXT_RESET;
+        assert_eq!(current_context.reset_status().unwrap(), ResetStatus::GuiltyContextReset);
+        assert_eq!(current_context.reset_status().unwrap(), ResetStatus::GuiltyContextReset);
+        assert_eq!(*reset_statuses.lock().unwrap(), vec![ResetStatus::GuiltyContextReset]);
+        assert!(current_context.sharing_group().is_lost());
+        match *buffer.bind(&current_context).unwrap_err().root() {
+            Error::ContextLost => (),
+            ref error => panic!("unexpected error {:?}", error),
+        }
+        assert_eq!(state.borrow().call_count("glBindBuffer"), 0);
+        drop(buffer);
+        assert_eq!(state.borrow().call_count("glDeleteBuffers"), 0);
+        let live_object_count = api.live_objects().len();
+
+        // No objects are created in the lost sharing group.
+        let assert_lost = |res: Result<(), Error>| match res {
+            Err(Error::ContextLost) => (),
+            res => panic!("unexpected result {:?}", res),
+        };
+        assert_lost(CurrentArrayBuffer::create(current_context.clone()).map(|_| ()));
+        assert_lost(CurrentVertexShader::create(current_context.clone()).map(|_| ()));
+        assert_lost(CurrentProgram::create(current_context.clone()).map(|_| ()));
+        #[cfg(any(feature = "gl33", feature = "gl45", feature = "gles3"))]
+        assert_lost(CurrentVertexArray::create_checked(current_context.clone()).map(|_| ()));
+        assert_eq!(state.borrow().call_count("glGenBuffers"), 1);
+        assert_eq!(state.borrow().call_count("glCreateShader"), 0);
+        assert_eq!(state.borrow().call_count("glCreateProgram"), 0);
+        assert_eq!(api.live_objects().len(), live_object_count);
+    }
+
+    #[test]
+    fn context_config_matches_bindings() {
+        let context_config = ContextConfig::default();
+        assert_eq!(context_config, ContextConfig::of_bindings());
+        assert_eq!(context_config.api_kind(), ApiKind::of_bindings());
+        let es_config = ContextConfig { version: Version::new(3, 2), profile: ContextProfile::Es, .. context_config.clone() };
+        assert_eq!(es_config.api_kind(), ApiKind::Gles);
+        assert!(!es_config.has_profile());
+        let core_config = ContextConfig { version: Version::new(3, 3), profile: ContextProfile::Core, .. context_config };
+        assert!(core_config.has_profile());
+    }
+
+    #[test]
+    fn gles_shader_body_gets_header() {
+        let api = Arc::new(ApiBuilder::new().kind(ApiKind::Gles).build(FakeMakeApiBackend).unwrap());
+        let (context, state) = make_context_(&api, FakeContextBackend::new());
+        let current_context = make_current(context).unwrap();
+        let shader = CurrentFragmentShader::create_from_body(current_context.clone(), "void main() {}\n").unwrap();
+        let source = state.borrow().objects.lock().unwrap().shaders[&shader.gl_handle()].source.clone();
+        assert!(source.starts_with(ApiKind::Gles.shader_header()));
+        assert!(source.contains("precision mediump float;"));
+        let error = CurrentFragmentShader::create_from_body(current_context, "void main() {\n#error broken\n}\n").err().unwrap();
+        match *error.root() {
+            Error::ShaderCompile(ref error) => {
+                assert_eq!(error.diagnostics[0].line, Some(2));
+                assert_eq!(error.diagnostics[0].source_line, Some("#error broken".to_string()));
+            },
+            ref error => panic!("unexpected error {:?}", error),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "glfw"))]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::rc::Rc;
+    use glfw;
+
+    #[test]
+    fn glfw_window_hints_of_config() {
+        let mut context_config = ContextConfig::of_bindings();
+        context_config.version = Version::new(3, 0);
+        context_config.profile = ContextProfile::Es;
+        context_config.forward_compatible = true;
+        context_config.robust = true;
+        context_config.samples = 4;
+        assert_eq!(glfw_window_hints(&context_config), vec![
+            glfw::WindowHint::ClientApi(glfw::ClientApiHint::OpenGlEs),
+            glfw::WindowHint::ContextVersion(3, 0),
+            glfw::WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Any),
+            glfw::WindowHint::OpenGlForwardCompat(false),
+            glfw::WindowHint::OpenGlDebugContext(false),
+            glfw::WindowHint::ContextRobustness(glfw::ContextRobustnessHint::LoseContextOnReset),
+            glfw::WindowHint::SRgbCapable(false),
+            glfw::WindowHint::DepthBits(24),
+            glfw::WindowHint::StencilBits(8),
+            glfw::WindowHint::Samples(4),
+        ]);
+    }
+
+    #[test]
+    fn glfw_window_hints_of_profile() {
+        let mut context_config = ContextConfig::of_bindings();
+        context_config.version = Version::new(2, 1);
+        context_config.profile = ContextProfile::Compatibility;
+        assert!(glfw_window_hints(&context_config).contains(&glfw::WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Any)));
+        context_config.version = Version::new(3, 2);
+        assert!(glfw_window_hints(&context_config).contains(&glfw::WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Compat)));
+        context_config.profile = ContextProfile::Core;
+        context_config.forward_compatible = true;
+        let hints = glfw_window_hints(&context_config);
+        assert!(hints.contains(&glfw::WindowHint::ClientApi(glfw::ClientApiHint::OpenGl)));
+        assert!(hints.contains(&glfw::WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Core)));
+        assert!(hints.contains(&glfw::WindowHint::OpenGlForwardCompat(true)));
     }
 
     #[test]
     fn it_works() {
-        let glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
-        let (mut window, events) = glfw.create_window(300, 300, "Test", glfw::WindowMode::Windowed).unwrap();
+        let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
+        let (mut window, _) = create_glfw_window(&mut glfw, &ContextConfig::of_bindings(), 300, 300, "Test").unwrap();
         glfw::Context::make_current(&mut window);
-        let api: Arc<Api> = { Arc::new(Api::new(&window)) };
-        let window = Rc::new(window);
-        let context: Rc<Context> = unsafe { Rc::new(Context::new(window, GlfwContextBackend(window))) };
-        let current_context = make_current(context);
-        /*
-        let ab = ArrayBuffer::new_with_data((&*cc, c.clone()), &[
+        let api = Arc::new(Api::new(GlfwMakeApiBackend::new(&mut window)).unwrap());
+        let context = Rc::new(unsafe { Context::new(api, Box::new(GlfwContextBackend::new(Rc::new(window)))) });
+        let current_context = make_current(context).unwrap();
+        let buffer = CurrentArrayBuffer::create(current_context.clone()).unwrap();
+        buffer.set_data(&current_context, &[
              1.0f32,  1.0f32,
             -1.0f32,  1.0f32,
             -1.0f32, -1.0f32,
              1.0f32, -1.0f32,
         ]).unwrap();
-        let eab = ElementArrayBuffer::new_with_data((&*cc, c.clone()), &[
-            0, 1, 2,
+        let element_buffer = CurrentElementArrayBuffer::create(current_context.clone()).unwrap();
+        element_buffer.set_data(&current_context, &[
+            0u16, 1, 2,
             2, 3, 0,
         ]).unwrap();
-        let p = Program::new_from_vertex_and_fragment_shader_src(
-            (&*cc, c.clone()),
-            "",
-            ""
-        ).unwrap();*/
     }
 }
@@ -0,0 +1,125 @@
+use std::backtrace::Backtrace;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+
+use gl::types::GLuint;
+
+use ObjectKind;
+use SharingGroupId;
+use ContextId;
+
+/// An object which was created through this library and is not deleted yet.
+#[derive(Clone, Debug)]
+pub struct LiveObject {
+    pub sharing_group: SharingGroupId,
+    /// The context of objects which can not be shared, like vertex arrays.
+    pub context: Option<ContextId>,
+    pub kind: ObjectKind,
+    pub gl_handle: GLuint,
+    pub label: Option<String>,
+    /// The backtrace of the creation. It is only captured if RUST_BACKTRACE or
+    /// RUST_LIB_BACKTRACE is set.
+    pub backtrace: String,
+}
+impl fmt::Display for LiveObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{} {}", self.kind, self.gl_handle));
+        if let Some(ref label) = self.label {
+            try!(write!(f, " \"{}\"", label));
+        }
+        write!(f, " created at:\n{}", self.backtrace)
+    }
+}
+
+/// Key of an object. Objects which can not be shared are identified by their context, because
+/// every context of the sharing group may use the same handle.
+type Key_ = (SharingGroupId, Option<ContextId>, ObjectKind, GLuint);
+
+struct Entry_ {
+    label_: Option<String>,
+    backtrace_: Backtrace,
+}
+
+/// Registry of the live objects of an api.
+pub(crate) struct ObjectRegistry {
+    objects_: Mutex<HashMap<Key_, Entry_>>,
+}
+impl ObjectRegistry {
+    pub fn new() -> Self {
+        ObjectRegistry {
+            objects_: Mutex::new(HashMap::new()),
+        }
+    }
+    /// Lock the objects. A poisoned lock is ignored, because the registry is used in drop.
+    fn lock_(&self) -> MutexGuard<HashMap<Key_, Entry_>> {
+        self.objects_.lock().unwrap_or_else(|error| error.into_inner())
+    }
+    /// Register an object, unless it is already registered.
+    /// Objects which are split into their handle stay registered, so that registering them again
+    /// keeps the backtrace of the creation.
+    pub fn register(&self, sharing_group: SharingGroupId, kind: ObjectKind, gl_handle: GLuint) {
+        self.register_(sharing_group, None, kind, gl_handle);
+    }
+    /// Set the label of a registered object.
+    pub fn set_label(&self, sharing_group: SharingGroupId, kind: ObjectKind, gl_handle: GLuint, label: &str) {
+        self.set_label_(sharing_group, None, kind, gl_handle, label);
+    }
+    /// Unregister a deleted object.
+    pub fn unregister(&self, sharing_group: SharingGroupId, kind: ObjectKind, gl_handle: GLuint) {
+        self.lock_().remove(&(sharing_group, None, kind, gl_handle));
+    }
+    /// Register an object of the context `context`, which can not be shared.
+    #[cfg(any(feature = "gl33", feature = "gl45", feature = "gles3"))]
+    pub fn register_unshared(&self, sharing_group: SharingGroupId, context: ContextId, kind: ObjectKind, gl_handle: GLuint) {
+        self.register_(sharing_group, Some(context), kind, gl_handle);
+    }
+    /// Set the label of a registered object of the context `context`.
+    #[cfg(any(feature = "gl33", feature = "gl45", feature = "gles3"))]
+    pub fn set_label_unshared(&self, sharing_group: SharingGroupId, context: ContextId, kind: ObjectKind, gl_handle: GLuint, label: &str) {
+        self.set_label_(sharing_group, Some(context), kind, gl_handle, label);
+    }
+    /// Unregister a deleted object of the context `context`.
+    #[cfg(any(feature = "gl33", feature = "gl45", feature = "gles3"))]
+    pub fn unregister_unshared(&self, sharing_group: SharingGroupId, context: ContextId, kind: ObjectKind, gl_handle: GLuint) {
+        self.lock_().remove(&(sharing_group, Some(context), kind, gl_handle));
+    }
+    fn register_(&self, sharing_group: SharingGroupId, context: Option<ContextId>, kind: ObjectKind, gl_handle: GLuint) {
+        if gl_handle != 0 {
+            self.lock_().entry((sharing_group, context, kind, gl_handle)).or_insert_with(|| Entry_ {
+                label_: None,
+                backtrace_: Backtrace::capture(),
+            });
+        }
+    }
+    fn set_label_(&self, sharing_group: SharingGroupId, context: Option<ContextId>, kind: ObjectKind, gl_handle: GLuint, label: &str) {
+        if let Some(entry) = self.lock_().get_mut(&(sharing_group, context, kind, gl_handle)) {
+            entry.label_ = Some(label.to_string());
+        }
+    }
+    /// Get the registered objects.
+    pub fn live_objects(&self) -> Vec<LiveObject> {
+        let mut live_objects: Vec<LiveObject> = self.lock_().iter().map(|(&key, entry)| live_object_(key, entry)).collect();
+        live_objects.sort_by_key(|live_object| (live_object.sharing_group, live_object.context, live_object.kind, live_object.gl_handle));
+        live_objects
+    }
+    /// Unregister the objects of the sharing group `sharing_group` and return them.
+    pub fn take_live_objects(&self, sharing_group: SharingGroupId) -> Vec<LiveObject> {
+        let mut objects = self.lock_();
+        let keys: Vec<Key_> = objects.keys().filter(|key| key.0 == sharing_group).cloned().collect();
+        let mut live_objects: Vec<LiveObject> = keys.into_iter().filter_map(|key| objects.remove(&key).map(|entry| live_object_(key, &entry))).collect();
+        live_objects.sort_by_key(|live_object| (live_object.context, live_object.kind, live_object.gl_handle));
+        live_objects
+    }
+}
+
+fn live_object_((sharing_group, context, kind, gl_handle): Key_, entry: &Entry_) -> LiveObject {
+    LiveObject {
+        sharing_group: sharing_group,
+        context: context,
+        kind: kind,
+        gl_handle: gl_handle,
+        label: entry.label_.clone(),
+        backtrace: entry.backtrace_.to_string(),
+    }
+}
@@ -1,214 +1,193 @@
 use std::rc::Rc;
-use std::sync::Arc;
-use std::marker::PhantomData;
-use std::ops::{Deref, DerefMut};
-use std::borrow::{Borrow, BorrowMut};
-use std::os::raw::c_void;
-use std::mem::{forget, transmute};
 
-use Error;
 use gl;
-use gl::types::GLuint;
-use GlError;
+use gl::types::{GLuint, GLint, GLenum, GLchar, GLsizei};
 
-use api;
-use DynApi;
+use Error;
+use ObjectKind;
 
 use CurrentContext;
-use MakeCurrent;
-use AlreadyCurrent;
-use WithCurrentContext;
-use WithMakeCurrent;
-
-use ContextState;
-
-use DynSharingGroup;
+use SharingGroupId;
 
-use VertexShader;
-use FragmentShader;
+use ShaderType;
+use CurrentShader;
+use CurrentVertexShader;
+use CurrentFragmentShader;
 
-use Handle;
-use HandleSpecifier;
-use HandleBorrow;
-use HandleBorrowMut;
-
-use std::cell::UnsafeCell;
-
-pub struct ProgramHandleSpecifier;
-impl HandleSpecifier for ProgramHandleSpecifier {
-    type Raw = GLuint;
-}
-
-pub type ProgramHandle = Handle<ProgramHandleSpecifier>;
-
-pub struct ProgramFacade<HB: HandleBorrow<ProgramHandleSpecifier>, WMC: WithMakeCurrent> {
-    handle_borrow_: HB,
-    with_make_current_: WMC,
-}
-impl<HB: HandleBorrow<ProgramHandleSpecifier>, WMC: WithMakeCurrent> ProgramFacade<HB, WMC> {
-    pub unsafe fn already_current_ref<'s, 'cc>(&'s self, current_context: &'cc CurrentContext) -> ProgramFacade<&ProgramHandle, (&'cc CurrentContext, AlreadyCurrent)> {
-        ProgramFacade {
-            handle_borrow_: self.handle_borrow_.borrow(),
-            with_make_current_: (current_context, AlreadyCurrent),
-        }
-    }
+/// A program which does not implement Sync or Send, but may be actually used.
+pub struct CurrentProgram {
+    gl_handle_: GLuint,
+    current_context_: Rc<CurrentContext>,
+    sharing_group_id_: SharingGroupId,
+    label_: Option<String>,
+}
+impl CurrentProgram {
+    /// Get the gl handle.
     pub fn gl_handle(&self) -> GLuint {
-        self.handle_borrow_.borrow().raw()
-    }
-    /*
-    pub fn attribute_location(&self, name: &str) -> Result<AttributeLocation, Error> {
-        self.with_make_current_.with_make_current(|current_context| {
-            unsafe {
-                let name = CString::new(name).unwrap();
-                let gl = api::gl(&*current_context.api_ptr());
-                AttributeLocation { gl_handle_: gl.GetAttribLocation(self.gl_handle(), name.as_ptr()) }
-            }
+        self.gl_handle_
+    }
+    /// Get the current context.
+    pub fn current_context(&self) -> &Rc<CurrentContext> {
+        &self.current_context_
+    }
+    /// Attach a shader.
+    /// Returns Error::WrongSharingGroup if the shader belongs to another sharing group.
+    pub fn attach_shader<Type: ShaderType>(&self, shader: &CurrentShader<Type>) -> Result<(), Error> {
+        try!(self.check_sharing_group_());
+        if shader.sharing_group_id() != self.sharing_group_id() {
+            return Err(Error::WrongSharingGroup.with_object(ObjectKind::Shader, shader.gl_handle(), shader.label()));
+        }
+        unsafe {
+            try!(self.current_context().require_functions(&[("glAttachShader", &self.current_context().gl().AttachShader)]));
+            self.current_context().gl().AttachShader(self.gl_handle(), shader.gl_handle());
+            try!(self.check_errors_("glAttachShader"));
+            Ok(())
         }
     }
-    */
-}
-impl<HB: HandleBorrow<ProgramHandleSpecifier>, WMC: WithMakeCurrent> ProgramFacade<HB, WMC> {
-    pub unsafe fn already_current_mut<'s, 'cc>(&'s mut self, current_context: &'cc CurrentContext) -> ProgramFacade<&'s mut HB, (&'cc CurrentContext, AlreadyCurrent)> {
-        ProgramFacade {
-            handle_borrow_: self.handle_borrow_.borrow_mut(),
-            with_make_current_: (current_context, AlreadyCurrent),
+    /// Get the id of the sharing group.
+    pub fn sharing_group_id(&self) -> SharingGroupId {
+        self.sharing_group_id_
+    }
+    /// Get the label set by set_label, which is added to the errors of the object.
+    pub fn label(&self) -> Option<&str> {
+        self.label_.as_ref().map(|label| &**label)
+    }
+    /// Check that the current context is part of the sharing group of the program.
+    fn check_sharing_group_(&self) -> Result<(), Error> {
+        self.current_context().check_sharing_group(self.sharing_group_id()).map_err(|error| error.with_object(ObjectKind::Program, self.gl_handle(), self.label()))
+    }
+    /// Set the label. It is passed to glObjectLabel if KHR_debug is supported.
+    pub fn set_label(&mut self, label: &str) -> Result<(), Error> {
+        try!(self.check_sharing_group_());
+        self.current_context().api().objects().set_label(self.sharing_group_id(), ObjectKind::Program, self.gl_handle(), label);
+        self.label_ = Some(label.to_string());
+        try!(self.current_context().object_label(gl::PROGRAM, self.gl_handle(), label).map_err(|error| error.with_object(ObjectKind::Program, self.gl_handle(), self.label())));
+        Ok(())
+    }
+    /// Check the errors of an opengl call with the program.
+    fn check_errors_(&self, call: &'static str) -> Result<(), Error> {
+        self.current_context().check_errors(call).map_err(|error| error.with_object(ObjectKind::Program, self.gl_handle(), self.label()))
+    }
+    /// Get a parameter using glGetProgramiv.
+    fn get_param_(&self, param: GLenum) -> Result<GLint, Error> {
+        try!(self.check_sharing_group_());
+        unsafe {
+            try!(self.current_context().require_functions(&[("glGetProgramiv", &self.current_context().gl().GetProgramiv)]));
+            let mut val: GLint = 0;
+            self.current_context().gl().GetProgramiv(self.gl_handle(), param, &mut val as *mut GLint);
+            try!(self.check_errors_("glGetProgramiv"));
+            Ok(val)
         }
     }
-    pub fn setup_from_vertex_and_fragment_shader_src(&mut self, vertex_shader_src: &str, fragment_shader_src: &str) -> Result<(), Error> {
-        self.handle_borrow_.borrow_mut();
-        self.with_make_current_.with_make_current(|current_context| {
-            unsafe {
-                let gl = api::gl(&*current_context.api_ptr());
-                let vertex_shader = try!(VertexShader::new_from_src((current_context, AlreadyCurrent), vertex_shader_src));
-                let fragment_shader = try!(FragmentShader::new_from_src((current_context, AlreadyCurrent), fragment_shader_src));
-                gl.AttachShader(self.gl_handle(), vertex_shader.gl_handle());
-                gl.AttachShader(self.gl_handle(), fragment_shader.gl_handle());
-                gl.LinkProgram(self.gl_handle());
-                Ok(())
-            }
-        })
+    /// Get the info log of the last link or validation.
+    pub fn info_log(&self) -> Result<String, Error> {
+        let info_log_len = try!(self.get_param_(gl::INFO_LOG_LENGTH));
+        if info_log_len <= 0 {
+            return Ok(String::new());
+        }
+        unsafe {
+            try!(self.current_context().require_functions(&[("glGetProgramInfoLog", &self.current_context().gl().GetProgramInfoLog)]));
+            let mut info_log: Vec<u8> = vec![0; info_log_len as usize];
+            let mut written_len: GLsizei = 0;
+            self.current_context().gl().GetProgramInfoLog(self.gl_handle(), info_log_len, &mut written_len as *mut GLsizei, info_log.as_mut_ptr() as *mut GLchar);
+            try!(self.check_errors_("glGetProgramInfoLog"));
+            info_log.truncate(written_len.max(0) as usize);
+            Ok(String::from_utf8_lossy(&info_log).into_owned())
+        }
     }
-}
-pub struct Program<WMC: WithMakeCurrent> {
-    program_facade_: ProgramFacade<ProgramHandle, WMC>,
-}
-impl<WMC: WithMakeCurrent> Deref for Program<WMC> {
-    type Target = ProgramFacade<ProgramHandle, WMC>;
-    fn deref(&self) -> &Self::Target {
-        &self.program_facade_
+    /// Whether the last link succeeded.
+    pub fn link_status(&self) -> Result<bool, Error> {
+        Ok(try!(self.get_param_(gl::LINK_STATUS)) != gl::FALSE as GLint)
     }
-}
-impl<WMC: WithMakeCurrent> DerefMut for Program<WMC> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.program_facade_
+    /// Link the attached shaders.
+    /// Returns Error::ProgramLink containing the info log, if the link failed.
+    pub fn link(&self) -> Result<(), Error> {
+        try!(self.check_sharing_group_());
+        unsafe {
+            try!(self.current_context().require_functions(&[("glLinkProgram", &self.current_context().gl().LinkProgram)]));
+            self.current_context().gl().LinkProgram(self.gl_handle());
+            try!(self.check_errors_("glLinkProgram"));
+        }
+        if try!(self.link_status()) {
+            Ok(())
+        } else {
+            Err(Error::ProgramLink(try!(self.info_log())).with_object(ObjectKind::Program, self.gl_handle(), self.label()))
+        }
     }
-}
-impl<WMC: WithMakeCurrent> Program<WMC> {
-    pub fn new_from_vertex_and_fragment_shader_src(with_make_current: WMC, vertex_shader_src: &str, fragment_shader_src: &str) -> Result<Self, Error> {
-        with_make_current.with_make_current(|current_context| {
-            unsafe {
-                let mut program = Program {
-                    program_facade_: ProgramFacade {
-                        handle_borrow_: ProgramHandle::new(api::gl(&*current_context.api_ptr()).CreateProgram()),
-                        with_make_current_: with_make_current.clone(),
-                    },
-                };
-                try!(program.already_current_mut(current_context).setup_from_vertex_and_fragment_shader_src(vertex_shader_src, fragment_shader_src));
-                Ok(program)
-            }
-        })
+    /// Validate the program against the current state using glValidateProgram.
+    /// Returns the validation log if the program is valid, and Error::ProgramValidate containing
+    /// the validation log otherwise.
+    pub fn validate(&self) -> Result<String, Error> {
+        try!(self.check_sharing_group_());
+        unsafe {
+            try!(self.current_context().require_functions(&[("glValidateProgram", &self.current_context().gl().ValidateProgram)]));
+            self.current_context().gl().ValidateProgram(self.gl_handle());
+            try!(self.check_errors_("glValidateProgram"));
+        }
+        let valid = try!(self.get_param_(gl::VALIDATE_STATUS)) != gl::FALSE as GLint;
+        let info_log = try!(self.info_log());
+        if valid {
+            Ok(info_log)
+        } else {
+            Err(Error::ProgramValidate(info_log).with_object(ObjectKind::Program, self.gl_handle(), self.label()))
+        }
     }
-}
-impl<WMC: WithMakeCurrent> Drop for Program<WMC> {
-    fn drop(&mut self) {
-        self.program_facade_.with_make_current_.with_make_current(|current_context| {
-            unsafe {
-                // TODO: Check errors.
-                api::gl(&*current_context.api_ptr()).DeleteProgram(self.gl_handle());
-                Ok(())
-            }
-        });
+    /// Unsafe create current program from gl handle.
+    pub unsafe fn from_gl_handle(gl_handle: GLuint, current_context: Rc<CurrentContext>) -> Self {
+        let sharing_group_id = current_context.sharing_group().id();
+        current_context.api().objects().register(sharing_group_id, ObjectKind::Program, gl_handle);
+        CurrentProgram {
+            gl_handle_: gl_handle,
+            current_context_: current_context,
+            sharing_group_id_: sharing_group_id,
+            label_: None,
+        }
     }
-}
-
-/*
-#[derive(Copy, Clone)]
-struct AttribLocation {
-    gl_handle_: GlAttribLocationHandle,
-}
-impl AttribLocation {
-    pub fn gl_handle() -> GlAttribLocationHandle {
-        gl_handle_
+    /// Create a new current program.
+    pub fn create(current_context: Rc<CurrentContext>) -> Result<Self, Error> {
+        try!(current_context.check_not_lost());
+        unsafe {
+            try!(current_context.require_functions(&[("glCreateProgram", &current_context.gl().CreateProgram), ("glDeleteProgram", &current_context.gl().DeleteProgram)]));
+            let gl_handle = current_context.gl().CreateProgram();
+            try!(current_context.check_errors("glCreateProgram").map_err(|error| error.with_object(ObjectKind::Program, gl_handle, None)));
+            Ok(Self::from_gl_handle(gl_handle, current_context))
+        }
     }
-}
-
-#[derive(Copy, Clone)]
-pub struct VertexAttribType(gl::types::GLenum);
-impl VertexAttribType {
-    pub unsafe fn new(t: gl::types::GLenum) -> Self {
-        VertexAttribType(t)
-    }
-    pub const BYTE: VertexAttribType = unsafe { VertexAttribType::new(gl::BYTE) };
-    pub const UNSIGNED_BYTE: VertexAttribType = unsafe { VertexAttribType::new(gl::UNSIGNED_BYTE) };
-    pub const SHORT: VertexAttribType = unsafe { VertexAttribType::new(gl::SHORT) };
-    pub const UNSIGNED_SHORT: VertexAttribType = unsafe { VertexAttribType::new(gl::UNSIGNED_SHORT) };
-    pub const INT: VertexAttribType = unsafe { VertexAttribType::new(gl::INT) };
-    pub const UNSIGNED_INT: VertexAttribType = unsafe { VertexAttribType::new(gl::UNSIGNED_INT) };
-    pub const HALF_FLOAT: VertexAttribType = unsafe { VertexAttribType::new(gl::HALF_FLOAT) };
-    pub const FLOAT: VertexAttribType = unsafe { VertexAttribType::new(gl::FLOAT) };
-    pub const DOUBLE: VertexAttribType = unsafe { VertexAttribType::new(gl::DOUBLE) };
-    pub const FIXED: VertexAttribType = unsafe { VertexAttribType::new(gl::FIXED) };
-    pub const INT_2_10_10_10_REV: VertexAttribType = unsafe { VertexAttribType::new(gl::INT_2_10_10_10_REV) };
-    pub const UNSIGNED_INT_2_10_10_10_REV: VertexAttribType = unsafe { VertexAttribType::new(gl::UNSIGNED_INT_2_10_10_10_REV) };
-    pub const UNSIGNED_INT_10F_11F_11F_REV: VertexAttribType = unsafe { VertexAttribType::new(gl::UNSIGNED_INT_10F_11F_11F_REV) };
-}
-impl Deref for VertexAttribType {
-    type Target = gl::types::GLenum;
-    fn deref(&self) -> &Self::Target {
-        self.0
+    /// Create a new current program from the sources of a vertex and a fragment shader.
+    pub fn create_from_vertex_and_fragment_shader_src(current_context: Rc<CurrentContext>, vertex_shader_src: &str, fragment_shader_src: &str) -> Result<Self, Error> {
+        let vertex_shader = try!(CurrentVertexShader::create_from_src(current_context.clone(), vertex_shader_src));
+        let fragment_shader = try!(CurrentFragmentShader::create_from_src(current_context.clone(), fragment_shader_src));
+        let program = try!(Self::create(current_context));
+        try!(program.attach_shader(&vertex_shader));
+        try!(program.attach_shader(&fragment_shader));
+        try!(program.link());
+        Ok(program)
     }
-}
-
-#[derive(Copy, Clone)]
-pub struct VertexAttribSize(gl::types::GLint);
-impl VertexAttribSize {
-    pub unsafe fn new(t: gl::types::GLint) -> Self {
-        VertexAttribType(t)
-    }
-    pub const _1: VertexAttribSize = unsafe { VertexAttribSize::new(1) };
-    pub const _2: VertexAttribSize = unsafe { VertexAttribSize::new(2) };
-    pub const _3: VertexAttribSize = unsafe { VertexAttribSize::new(3) };
-    pub const _4: VertexAttribSize = unsafe { VertexAttribSize::new(4) };
-}
-impl Deref for VertexAttribSize {
-    type Target = gl::types::GLenum;
-    fn deref(&self) -> &Self::Target {
-        self.0
+    /// Create a new current program from the bodies of a vertex and a fragment shader, which are
+    /// prepended by the shader header of the kind of the api.
+    pub fn create_from_vertex_and_fragment_shader_body(current_context: Rc<CurrentContext>, vertex_shader_body: &str, fragment_shader_body: &str) -> Result<Self, Error> {
+        let vertex_shader = try!(CurrentVertexShader::create_from_body(current_context.clone(), vertex_shader_body));
+        let fragment_shader = try!(CurrentFragmentShader::create_from_body(current_context.clone(), fragment_shader_body));
+        let program = try!(Self::create(current_context));
+        try!(program.attach_shader(&vertex_shader));
+        try!(program.attach_shader(&fragment_shader));
+        try!(program.link());
+        Ok(program)
     }
 }
-
-pub struct VertexAttrib {
-    location_: AttribLocation,
-    ArrayBuffer,
-    type_: VertexAttribType,
-    size_: VertexAttribSize,
-    stride_: usize,
-    offset_: usize,
-}
-impl VertexAttrib {
-    pub fn new(location: AttribLocation, type: VertexAttribType, size: VertexAttribSize, stride: usize, offset: usize) -> Self {
-        VertexAttribPtr {
-            location_: location,
-            type_: type,
-            size_: size,
-            stride_: stride,
-            offset_: offset,
+impl Drop for CurrentProgram {
+    fn drop(&mut self) {
+        unsafe {
+            // The program can not be deleted while a context of another sharing group is current,
+            // so it stays registered and is reported as leak.
+            if self.gl_handle() != 0 && self.current_context().gl().DeleteProgram.is_loaded() && self.check_sharing_group_().is_ok() {
+                self.current_context().gl().DeleteProgram(self.gl_handle());
+                // Errors can not be propagated out of drop, so they are passed to the error hook.
+                if let Err(error) = self.current_context().check_errors("glDeleteProgram") {
+                    self.current_context().api().report_drop_error("glDeleteProgram", error);
+                }
+                self.current_context().api().objects().unregister(self.sharing_group_id(), ObjectKind::Program, self.gl_handle());
+            }
         }
     }
-    pub fn location(&self) -> AttributeLocation { self.location_ }
-    pub fn type(&self) -> VertexAttribType { self.type_ }
-    pub fn size(&self) -> VertexAttribSize { self.size_ }
-    pub fn stride(&self) -> usize { self.stride_ }
-    pub fn offset(&self) -> usize { self.offset_ }
 }
-*/
@@ -0,0 +1,55 @@
+use gl;
+use gl::types::GLenum;
+
+/// Status of a context returned by glGetGraphicsResetStatus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetStatus {
+    /// GL_NO_ERROR, the context was not reset.
+    NoError,
+    /// GL_GUILTY_CONTEXT_RESET, the context caused the reset.
+    GuiltyContextReset,
+    /// GL_INNOCENT_CONTEXT_RESET, another context caused the reset.
+    InnocentContextReset,
+    /// GL_UNKNOWN_CONTEXT_RESET, the cause of the reset is unknown.
+    UnknownContextReset,
+}
+impl ResetStatus {
+    /// Get the status from a value returned by glGetGraphicsResetStatus.
+    /// Unknown values are treated as GL_UNKNOWN_CONTEXT_RESET.
+    pub fn from_enum_val(enum_val: GLenum) -> Self {
+        match enum_val {
+            gl::NO_ERROR => ResetStatus::NoError,
+            gl::GUILTY_CONTEXT_RESET => ResetStatus::GuiltyContextReset,
+            gl::INNOCENT_CONTEXT_RESET => ResetStatus::InnocentContextReset,
+            _ => ResetStatus::UnknownContextReset,
+        }
+    }
+}
+
+/// Call glGetGraphicsResetStatus, or its variant of ARB_robustness or EXT_robustness.
+/// Returns None if none of them is loaded.
+pub(crate) unsafe fn get_graphics_reset_status(gl: &gl::Gl) -> Option<GLenum> {
+    if gl.GetGraphicsResetStatus.is_loaded() {
+        Some(gl.GetGraphicsResetStatus())
+    } else {
+        get_graphics_reset_status_vendor_(gl)
+    }
+}
+
+#[cfg(not(any(feature = "gles2", feature = "gles3")))]
+unsafe fn get_graphics_reset_status_vendor_(gl: &gl::Gl) -> Option<GLenum> {
+    if gl.GetGraphicsResetStatusARB.is_loaded() {
+        Some(gl.GetGraphicsResetStatusARB())
+    } else {
+        None
+    }
+}
+
+#[cfg(any(feature = "gles2", feature = "gles3"))]
+unsafe fn get_graphics_reset_status_vendor_(gl: &gl::Gl) -> Option<GLenum> {
+    if gl.GetGraphicsResetStatusEXT.is_loaded() {
+        Some(gl.GetGraphicsResetStatusEXT())
+    } else {
+        None
+    }
+}
@@ -1,145 +1,322 @@
 use std::rc::Rc;
-use std::sync::Arc;
-use std::os::raw::c_void;
-use std::mem::forget;
+use std::marker::PhantomData;
+use std::fmt;
 
 use gl;
-use gl::types::GLuint;
+use gl::types::{GLuint, GLint, GLchar, GLsizei};
 
 use Error;
+use ObjectKind;
 
-use Api;
 use CurrentContext;
-use StateCache;
+use SharingGroupId;
 
+/// Stage of the pipeline a shader is used for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ShaderStage::Vertex => write!(f, "vertex"),
+            ShaderStage::Fragment => write!(f, "fragment"),
+        }
+    }
+}
+
+/// Type specific enumeration values for shaders.
 pub unsafe trait ShaderType {
     /// Get the type enumeration value. This should not change.
     fn enum_val() -> gl::types::GLenum;
+    /// Get the stage. This should not change.
+    fn stage() -> ShaderStage;
 }
 
-pub struct CurrentShader {
-    gl_handle_: GLuint,
-    current_context_: Rc<CurrentContext>,
+/// A diagnostic of the info log of a shader compilation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    /// The line of the source the diagnostic refers to, starting at 1.
+    pub line: Option<usize>,
+    /// The message without the location.
+    pub message: String,
+    /// The line of the source the diagnostic refers to.
+    pub source_line: Option<String>,
 }
-impl CurrentShader {
-    pub fn gl_handle(&self) -> GLuint
+
+/// The compilation of a shader failed.
+#[derive(Clone, Debug)]
+pub struct ShaderCompileError {
+    /// The stage of the shader.
+    pub stage: ShaderStage,
+    /// The info log as reported by the driver.
+    pub info_log: String,
+    /// The diagnostics parsed from the info log.
+    pub diagnostics: Vec<ShaderDiagnostic>,
 }
 
-pub struct ShaderFacade<T: ShaderType, HB: HandleBorrow<ShaderHandleSpecifier<T>>, WMC: WithMakeCurrent> {
-    handle_borrow_: HB,
-    with_make_current_: WMC,
-    phantom_target_: PhantomData<T>,
+impl fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => try!(write!(f, "line {}: {}", line, self.message)),
+            None => try!(write!(f, "{}", self.message)),
+        }
+        if let Some(ref source_line) = self.source_line {
+            try!(write!(f, "\n    | {}", source_line));
+        }
+        Ok(())
+    }
 }
-impl<T: ShaderType, HB: HandleBorrow<ShaderHandleSpecifier<T>>, WMC: WithMakeCurrent> ShaderFacade<T, HB, WMC> {
-    pub unsafe fn already_current_ref<'s, 'cc>(&'s self, current_context: &'cc CurrentContext) -> ShaderFacade<T, &ShaderHandle<T>, (&'cc CurrentContext, AlreadyCurrent)> {
-        ShaderFacade {
-            handle_borrow_: self.handle_borrow_.borrow(),
-            with_make_current_: (current_context, AlreadyCurrent),
-            phantom_target_: PhantomData,
+impl fmt::Display for ShaderCompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{} shader compilation failed", self.stage));
+        for diagnostic in &self.diagnostics {
+            try!(write!(f, "\n{}", diagnostic));
         }
+        Ok(())
     }
-    pub fn gl_handle(&self) -> GLuint {
-        self.handle_borrow_.borrow().raw()
+}
+
+/// Parse a decimal number at the start of `s`. Returns the number and the rest.
+fn parse_number_(s: &str) -> Option<(usize, &str)> {
+    let end = s.find(|c: char| !c.is_digit(10)).unwrap_or(s.len());
+    if end == 0 {
+        None
+    } else {
+        s[..end].parse().ok().map(|number| (number, &s[end..]))
     }
 }
-impl<T: ShaderType, HB: HandleBorrow<ShaderHandleSpecifier<T>>, WMC: WithMakeCurrent> ShaderFacade<T, HB, WMC> {
-    pub unsafe fn already_current_mut<'s, 'cc>(&'s mut self, current_context: &'cc CurrentContext) -> ShaderFacade<T, &'s mut HB, (&'cc CurrentContext, AlreadyCurrent)> {
-        ShaderFacade {
-            handle_borrow_: self.handle_borrow_.borrow_mut(),
-            with_make_current_: (current_context, AlreadyCurrent),
-            phantom_target_: PhantomData,
-        }
+
+/// Parse the location at the start of a line of an info log. Returns the line and the rest.
+/// Understands the formats `0:12(5): ...` (mesa), `0:12: ...` (amd, intel, apple) and
+/// `0(12) : ...` (nvidia).
+fn parse_location_(s: &str) -> Option<(usize, &str)> {
+    let rest = match parse_number_(s) {
+        Some((_, rest)) => rest,
+        None => return None,
+    };
+    if rest.starts_with(':') {
+        let (line, rest) = match parse_number_(&rest[1..]) {
+            Some(res) => res,
+            None => return None,
+        };
+        let rest = if rest.starts_with('(') {
+            match rest.find(')') {
+                Some(end) => &rest[end + 1..],
+                None => return None,
+            }
+        } else {
+            rest
+        };
+        let rest = rest.trim_left();
+        if rest.starts_with(':') { Some((line, &rest[1..])) } else { None }
+    } else if rest.starts_with('(') {
+        let (line, rest) = match parse_number_(&rest[1..]) {
+            Some(res) => res,
+            None => return None,
+        };
+        let rest = if rest.starts_with(')') { rest[1..].trim_left() } else { return None };
+        if rest.starts_with(':') { Some((line, &rest[1..])) } else { None }
+    } else {
+        None
     }
-    pub fn setup_from_src(&mut self, src: &str) -> Result<(), Error> {
-        self.handle_borrow_.borrow_mut();
-        self.with_make_current_.with_make_current(|current_context| {
-            unsafe {
-                let gl = api::gl(&*current_context.api_ptr());
-                let ptr: *const *const gl::types::GLchar = transmute(&src.as_ptr() as *const *const u8);
-                gl.ShaderSource(self.gl_handle(), 1, ptr, &(src.len() as gl::types::GLint) as *const gl::types::GLint);
-                gl.CompileShader(self.gl_handle());
-                Ok(())
+}
+
+/// Parse the info log of a shader compilation and map the diagnostics to the lines of `src`.
+pub fn parse_shader_info_log(info_log: &str, src: &str) -> Vec<ShaderDiagnostic> {
+    let src_lines: Vec<&str> = src.lines().collect();
+    info_log.lines()
+        .map(|log_line| log_line.trim())
+        .filter(|log_line| !log_line.is_empty())
+        .map(|log_line| {
+            let (prefix, located) = if log_line.starts_with("ERROR: ") {
+                ("error: ", &log_line[7..])
+            } else if log_line.starts_with("WARNING: ") {
+                ("warning: ", &log_line[9..])
+            } else {
+                ("", log_line)
+            };
+            match parse_location_(located) {
+                Some((line, rest)) => ShaderDiagnostic {
+                    line: Some(line),
+                    message: format!("{}{}", prefix, rest.trim()),
+                    source_line: if line >= 1 { src_lines.get(line - 1).map(|src_line| src_line.to_string()) } else { None },
+                },
+                None => ShaderDiagnostic {
+                    line: None,
+                    message: log_line.to_string(),
+                    source_line: None,
+                },
             }
         })
-    }
+        .collect()
 }
-pub struct Shader<T: ShaderType, WMC: WithMakeCurrent> {
-    shader_facade_: ShaderFacade<T, ShaderHandle<T>, WMC>,
+
+/// A shader which does not implement Sync or Send, but may be actually used.
+pub struct CurrentShader<Type: ShaderType> {
+    gl_handle_: GLuint,
+    current_context_: Rc<CurrentContext>,
+    sharing_group_id_: SharingGroupId,
+    label_: Option<String>,
+    phantom_type_: PhantomData<Type>,
 }
-impl<T: ShaderType, WMC: WithMakeCurrent> Deref for Shader<T, WMC> {
-    type Target = ShaderFacade<T, ShaderHandle<T>, WMC>;
-    fn deref(&self) -> &Self::Target {
-        &self.shader_facade_
+impl<Type: ShaderType> CurrentShader<Type> {
+    /// Get the gl handle.
+    pub fn gl_handle(&self) -> GLuint {
+        self.gl_handle_
     }
-}
-impl<T: ShaderType, WMC: WithMakeCurrent> DerefMut for Shader<T, WMC> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.shader_facade_
+    /// Get the current context.
+    pub fn current_context(&self) -> &Rc<CurrentContext> {
+        &self.current_context_
     }
-}
-impl<T: ShaderType, WMC: WithMakeCurrent> Shader<T, WMC> {
-    pub fn new_from_src(with_make_current: WMC, src: &str) -> Result<Self, Error> {
-        with_make_current.with_make_current(|current_context| {
-            unsafe {
-                let mut shader = Shader {
-                    shader_facade_: ShaderFacade {
-                        handle_borrow_: ShaderHandle::new(api::gl(&*current_context.api_ptr()).CreateShader(T::enum_val())),
-                        with_make_current_: with_make_current.clone(),
-                        phantom_target_: PhantomData,
-                    },
-                };
-                try!(shader.already_current_mut(current_context).setup_from_src(src));
-                Ok(shader)
+    /// Get the id of the sharing group.
+    pub fn sharing_group_id(&self) -> SharingGroupId {
+        self.sharing_group_id_
+    }
+    /// Get the label set by set_label, which is added to the errors of the object.
+    pub fn label(&self) -> Option<&str> {
+        self.label_.as_ref().map(|label| &**label)
+    }
+    /// Check that the current context is part of the sharing group of the shader.
+    fn check_sharing_group_(&self) -> Result<(), Error> {
+        self.current_context().check_sharing_group(self.sharing_group_id()).map_err(|error| error.with_object(ObjectKind::Shader, self.gl_handle(), self.label()))
+    }
+    /// Set the label. It is passed to glObjectLabel if KHR_debug is supported.
+    pub fn set_label(&mut self, label: &str) -> Result<(), Error> {
+        try!(self.check_sharing_group_());
+        self.current_context().api().objects().set_label(self.sharing_group_id(), ObjectKind::Shader, self.gl_handle(), label);
+        self.label_ = Some(label.to_string());
+        try!(self.current_context().object_label(gl::SHADER, self.gl_handle(), label).map_err(|error| error.with_object(ObjectKind::Shader, self.gl_handle(), self.label())));
+        Ok(())
+    }
+    /// Check the errors of an opengl call with the shader.
+    fn check_errors_(&self, call: &'static str) -> Result<(), Error> {
+        self.current_context().check_errors(call).map_err(|error| error.with_object(ObjectKind::Shader, self.gl_handle(), self.label()))
+    }
+    /// Get the info log of the last compilation.
+    pub fn info_log(&self) -> Result<String, Error> {
+        try!(self.check_sharing_group_());
+        unsafe {
+            let gl = self.current_context().gl();
+            try!(self.current_context().require_functions(&[("glGetShaderiv", &gl.GetShaderiv), ("glGetShaderInfoLog", &gl.GetShaderInfoLog)]));
+            let mut info_log_len: GLint = 0;
+            gl.GetShaderiv(self.gl_handle(), gl::INFO_LOG_LENGTH, &mut info_log_len as *mut GLint);
+            try!(self.check_errors_("glGetShaderiv"));
+            if info_log_len <= 0 {
+                return Ok(String::new());
             }
-        })
+            let mut info_log: Vec<u8> = vec![0; info_log_len as usize];
+            let mut written_len: GLsizei = 0;
+            gl.GetShaderInfoLog(self.gl_handle(), info_log_len, &mut written_len as *mut GLsizei, info_log.as_mut_ptr() as *mut GLchar);
+            try!(self.check_errors_("glGetShaderInfoLog"));
+            info_log.truncate(written_len.max(0) as usize);
+            Ok(String::from_utf8_lossy(&info_log).into_owned())
+        }
     }
-}
-impl<T: ShaderType, WMC: WithMakeCurrent> Drop for Shader<T, WMC> {
-    fn drop(&mut self) {
-        self.shader_facade_.with_make_current_.with_make_current(|current_context| {
-            unsafe {
-                // TODO: Check errors.
-                api::gl(&*current_context.api_ptr()).DeleteShader(self.gl_handle());
-                Ok(())
+    /// Whether the last compilation succeeded.
+    pub fn compile_status(&self) -> Result<bool, Error> {
+        try!(self.check_sharing_group_());
+        unsafe {
+            try!(self.current_context().require_functions(&[("glGetShaderiv", &self.current_context().gl().GetShaderiv)]));
+            let mut compile_status: GLint = 0;
+            self.current_context().gl().GetShaderiv(self.gl_handle(), gl::COMPILE_STATUS, &mut compile_status as *mut GLint);
+            try!(self.check_errors_("glGetShaderiv"));
+            Ok(compile_status != gl::FALSE as GLint)
+        }
+    }
+    /// Set the source of the shader and compile it.
+    /// Returns Error::ShaderCompile containing the info log, if the compilation failed.
+    pub fn compile_src(&self, src: &str) -> Result<(), Error> {
+        self.compile_(src, 0)
+    }
+    /// Compile the body of a shader, prepended by the header of the kind of the api.
+    /// The lines of the diagnostics refer to the body.
+    pub fn compile_body(&self, body: &str) -> Result<(), Error> {
+        let header = self.current_context().api().kind().shader_header();
+        self.compile_(&format!("{}{}", header, body), header.lines().count())
+    }
+    /// Compile the source `src`, whose first `header_lines` lines are not part of the source
+    /// written by the user.
+    fn compile_(&self, src: &str, header_lines: usize) -> Result<(), Error> {
+        try!(self.check_sharing_group_());
+        unsafe {
+            let gl = self.current_context().gl();
+            try!(self.current_context().require_functions(&[("glShaderSource", &gl.ShaderSource), ("glCompileShader", &gl.CompileShader)]));
+            let src_ptr = src.as_ptr() as *const GLchar;
+            let src_len = src.len() as GLint;
+            gl.ShaderSource(self.gl_handle(), 1, &src_ptr as *const *const GLchar, &src_len as *const GLint);
+            try!(self.check_errors_("glShaderSource"));
+            gl.CompileShader(self.gl_handle());
+            try!(self.check_errors_("glCompileShader"));
+        }
+        if try!(self.compile_status()) {
+            Ok(())
+        } else {
+            let info_log = try!(self.info_log());
+            let mut diagnostics = parse_shader_info_log(&info_log, src);
+            if header_lines != 0 {
+                // Diagnostics within the header have no line of the body.
+                for diagnostic in &mut diagnostics {
+                    diagnostic.line = diagnostic.line.and_then(|line| if line > header_lines { Some(line - header_lines) } else { None });
+                }
             }
-        });
+            Err(Error::ShaderCompile(ShaderCompileError {
+                stage: Type::stage(),
+                info_log: info_log,
+                diagnostics: diagnostics,
+            }).with_object(ObjectKind::Shader, self.gl_handle(), self.label()))
+        }
     }
-}
-
-
-
-
-
-/*
-pub struct ActiveShader<T: ShaderType> {
-    gl_handle_borrow_: GlShaderHandle,
-    actice_context_: Rc<ActiveContext>,
-    phantom_type_: PhantomData<T>,
-}
-impl<T: ShaderType> Shader<T> {
-    pub unsafe fn current_new(src: &str, ac: &Rc<ActiveContext>) -> Result<Self, Error> {
-        let mut ac = ActiveShader{
-            gl_handle_: T::create_shader(ac.api()),
-            active_context_: ac.clone(),
+    /// Unsafe create current shader from gl handle.
+    pub unsafe fn from_gl_handle(gl_handle: GLuint, current_context: Rc<CurrentContext>) -> Self {
+        let sharing_group_id = current_context.sharing_group().id();
+        current_context.api().objects().register(sharing_group_id, ObjectKind::Shader, gl_handle);
+        CurrentShader {
+            gl_handle_: gl_handle,
+            current_context_: current_context,
+            sharing_group_id_: sharing_group_id,
+            label_: None,
             phantom_type_: PhantomData,
         }
-        try!(ac.current_setup(src));
-        Ok(ac)
-    }
-    pub fn new(src: &str, ac: &Rc<ActiveContext>) -> Result<Self, Error> {
-        try!(ac.make_current());
-        unsafe { Self::current_new(src, ac) }
-    }
-    pub unsafe fn current_setup(&mut self, src: &str) -> Result<(), Error> {
-        // TODO: Error checking
-        let gl = api::gl(active_context_.api().deref());
-        gl.ShaderSource(self.gl_handle, 1, &src.as_ptr() as *const *const types::GLchar, &(src.len() as types::GLint) as *const types::GLint);
-        gl.
-        Ok(())
     }
-    pub fn setup(&mut self, src: &str) -> Result<(), Error> {
-        try!(self.active_context_.make_current());
-        unsafe { self.current_setup(src) }
+    /// Create a new current shader.
+    pub fn create(current_context: Rc<CurrentContext>) -> Result<Self, Error> {
+        try!(current_context.check_not_lost());
+        unsafe {
+            try!(current_context.require_functions(&[("glCreateShader", &current_context.gl().CreateShader), ("glDeleteShader", &current_context.gl().DeleteShader)]));
+            let gl_handle = current_context.gl().CreateShader(Type::enum_val());
+            try!(current_context.check_errors("glCreateShader").map_err(|error| error.with_object(ObjectKind::Shader, gl_handle, None)));
+            Ok(Self::from_gl_handle(gl_handle, current_context))
+        }
+    }
+    /// Create a new current shader and compile it from source.
+    pub fn create_from_src(current_context: Rc<CurrentContext>, src: &str) -> Result<Self, Error> {
+        let shader = try!(Self::create(current_context));
+        try!(shader.compile_src(src));
+        Ok(shader)
+    }
+    /// Create a new current shader and compile it from a body without #version directive.
+    pub fn create_from_body(current_context: Rc<CurrentContext>, body: &str) -> Result<Self, Error> {
+        let shader = try!(Self::create(current_context));
+        try!(shader.compile_body(body));
+        Ok(shader)
+    }
+}
+impl<Type: ShaderType> Drop for CurrentShader<Type> {
+    fn drop(&mut self) {
+        unsafe {
+            // The shader can not be deleted while a context of another sharing group is current,
+            // so it stays registered and is reported as leak.
+            if self.gl_handle() != 0 && self.current_context().gl().DeleteShader.is_loaded() && self.check_sharing_group_().is_ok() {
+                self.current_context().gl().DeleteShader(self.gl_handle());
+                // Errors can not be propagated out of drop, so they are passed to the error hook.
+                if let Err(error) = self.current_context().check_errors("glDeleteShader") {
+                    self.current_context().api().report_drop_error("glDeleteShader", error);
+                }
+                self.current_context().api().objects().unregister(self.sharing_group_id(), ObjectKind::Shader, self.gl_handle());
+            }
+        }
     }
 }
-*/
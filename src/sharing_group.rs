@@ -1,41 +1,102 @@
 use Error;
-use DynApi;
-use DynContext;
+use Api;
+use ContextBackend;
+use Context;
 
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::rc::Rc;
 
+/// The id of the next sharing group.
+static NEXT_SHARING_GROUP_ID_: AtomicUsize = AtomicUsize::new(1);
+
+/// Identity of a sharing group, which is unique within the process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SharingGroupId(usize);
+
 /// Backend of a sharing group.
 /// A sharing group is a group of contexts which share their objects.
 /// It may be empty. In this case the implementations should internally own a context to guarantee
 /// that the objects live on.
-/// TODO: Make it sync.
-pub unsafe trait SharingGroupBackend: 'static {
-    /// Create a new context.
+/// Contexts of the group may be created and made current in different threads, so the backend
+/// has to be Send and Sync.
+pub unsafe trait SharingGroupBackend: Send + Sync + 'static {
+    /// Create a new context, which shares its objects with the other contexts of the group.
+    /// It is called in the thread in which the context will be current.
     unsafe fn create_context(&self) -> Result<Box<ContextBackend>, Error>;
 }
 
 /// Frontend of a sharing group.
 /// A sharing group is a group of contexts which share their objects.
-/// It may be empty. In this case the objects are guaranteed to live on,
+/// Every object records the sharing group it was created in, and may only be used while a
+/// context of this group is current.
+/// Contexts created without a sharing group get a sharing group of their own, which can not
+/// create further contexts.
+/// Objects which are still alive when the sharing group is dropped are reported as
+/// Error::Leaked to the error hook of the api, unless the sharing group was lost by a reset of
+/// the context.
+/// It implements Send and Sync, so every thread may create its own context in the group. Objects
+/// created in one thread may be used in another one after a sync point like glFinish, see
+/// CurrentBuffer::into_async.
 pub struct SharingGroup {
+    id_: SharingGroupId,
     api_: Arc<Api>,
-    backend_: Box<SharingGroupBackend>,
+    backend_: Option<Box<SharingGroupBackend>>,
+    lost_: AtomicBool,
 }
 impl SharingGroup {
-    pub unsafe fn new(api: Arc<Api>, backend: Box<SharingGroupBackend>) -> SharingGroup {
-        SharingGroup {
+    /// Create a sharing group.
+    /// It is unsafe, because the backend has to create contexts of the api.
+    pub unsafe fn new(api: Arc<Api>, backend: Box<SharingGroupBackend>) -> Arc<SharingGroup> {
+        Arc::new(SharingGroup {
+            id_: SharingGroupId(NEXT_SHARING_GROUP_ID_.fetch_add(1, Ordering::Relaxed)),
             api_: api,
-            backend_: backend,
-        }
+            backend_: Some(backend),
+            lost_: AtomicBool::new(false),
+        })
     }
-    pub fn from_api(api: Arc<Api>) -> Self {
-        let backend = api.backend().create_sharing_group();
-        SharingGroup{
+    /// Create the sharing group of a single context.
+    pub(crate) fn new_implicit(api: Arc<Api>) -> Arc<SharingGroup> {
+        Arc::new(SharingGroup {
+            id_: SharingGroupId(NEXT_SHARING_GROUP_ID_.fetch_add(1, Ordering::Relaxed)),
             api_: api,
-            backend_: backend
+            backend_: None,
+            lost_: AtomicBool::new(false),
+        })
+    }
+    /// Create a new context of the sharing group.
+    /// Returns Error::Unsupported for the sharing group of a single context.
+    pub fn create_context(this: &Arc<Self>) -> Result<Rc<Context>, Error> {
+        match this.backend_ {
+            Some(ref backend) => unsafe {
+                let context_backend = try!(backend.create_context());
+                Ok(Rc::new(Context::new_in_sharing_group(this.clone(), context_backend)))
+            },
+            None => Err(Error::Unsupported("context creation in the sharing group of a single context")),
+        }
+    }
+    /// Get the identity.
+    pub fn id(&self) -> SharingGroupId { self.id_ }
+    /// Get the backend. It is None for the sharing group of a single context.
+    pub fn backend(&self) -> Option<&SharingGroupBackend> { self.backend_.as_ref().map(|backend| &**backend) }
+    /// Get the api.
+    pub fn api(&self) -> &Arc<Api> { &self.api_ }
+    /// Whether a context of the sharing group was reset, see CurrentContext::reset_status.
+    /// The objects of a lost sharing group are invalid and have to be recreated in a new one.
+    pub fn is_lost(&self) -> bool {
+        self.lost_.load(Ordering::SeqCst)
+    }
+    /// Mark the sharing group as lost. Returns whether it was not lost before.
+    pub(crate) fn mark_lost(&self) -> bool {
+        !self.lost_.swap(true, Ordering::SeqCst)
+    }
+}
+impl Drop for SharingGroup {
+    fn drop(&mut self) {
+        let live_objects = self.api_.objects().take_live_objects(self.id_);
+        // The objects of a lost sharing group can not be deleted, so they are not reported.
+        if !self.is_lost() && !live_objects.is_empty() {
+            self.api_.report_drop_error("dropping the sharing group", Error::Leaked(live_objects));
         }
     }
-    pub fn backend(&self) -> &Backend { &self.backend_ }
-    pub fn api(&self) -> &Arc<DynApi> { &self.api_ }
 }
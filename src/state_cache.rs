@@ -10,6 +10,8 @@ pub struct StateCache {
     pub bound_array_buffer_gl_handle: Cell<GLuint>,
     /// The handle of the bound index-buffer.
     pub bound_element_array_buffer_gl_handle: Cell<GLuint>,
+    /// The handle of the bound vertex array.
+    pub bound_vertex_array_gl_handle: Cell<GLuint>,
     // ... TODO
 }
 impl StateCache {
@@ -17,10 +19,18 @@ impl StateCache {
         StateCache {
             bound_array_buffer_gl_handle: Cell::new(0),
             bound_element_array_buffer_gl_handle: Cell::new(0),
+            bound_vertex_array_gl_handle: Cell::new(0),
         }
     }
+    /// Copy the recorded state of another state cache.
+    pub fn copy_from(&self, other: &StateCache) {
+        self.bound_array_buffer_gl_handle.set(other.bound_array_buffer_gl_handle.get());
+        self.bound_element_array_buffer_gl_handle.set(other.bound_element_array_buffer_gl_handle.get());
+        self.bound_vertex_array_gl_handle.set(other.bound_vertex_array_gl_handle.get());
+    }
     pub fn clear(&self) {
         self.bound_array_buffer_gl_handle.set(0);
         self.bound_element_array_buffer_gl_handle.set(0);
+        self.bound_vertex_array_gl_handle.set(0);
     }
 }
@@ -0,0 +1,126 @@
+use std::rc::{Rc, Weak};
+use std::ops::Deref;
+use std::marker::PhantomData;
+
+use Error;
+use ApiKind;
+use Version;
+use Context;
+use CurrentContext;
+
+/// Marker of an opengl version, which is used to check the availability of procedures at
+/// compile time.
+pub unsafe trait ApiVersion: 'static {
+    /// Get the name, for example "OpenGL 3.3".
+    fn name() -> &'static str;
+    /// Get the kind of the api.
+    fn kind() -> ApiKind;
+    /// Get the minimal version.
+    fn version() -> Version;
+}
+
+/// Marker of versions with vertex array objects.
+pub unsafe trait HasVertexArrays: ApiVersion {}
+/// Marker of versions with immutable buffer storage.
+pub unsafe trait HasBufferStorage: ApiVersion {}
+
+/// Opengl 2.0.
+pub enum Gl20 {}
+/// Opengl 3.3.
+pub enum Gl33 {}
+/// Opengl 4.5.
+pub enum Gl45 {}
+/// Opengl es 2.0.
+pub enum Gles20 {}
+/// Opengl es 3.0.
+pub enum Gles30 {}
+
+unsafe impl ApiVersion for Gl20 {
+    fn name() -> &'static str { "OpenGL 2.0" }
+    fn kind() -> ApiKind { ApiKind::Gl }
+    fn version() -> Version { Version::new(2, 0) }
+}
+unsafe impl ApiVersion for Gl33 {
+    fn name() -> &'static str { "OpenGL 3.3" }
+    fn kind() -> ApiKind { ApiKind::Gl }
+    fn version() -> Version { Version::new(3, 3) }
+}
+unsafe impl ApiVersion for Gl45 {
+    fn name() -> &'static str { "OpenGL 4.5" }
+    fn kind() -> ApiKind { ApiKind::Gl }
+    fn version() -> Version { Version::new(4, 5) }
+}
+unsafe impl ApiVersion for Gles20 {
+    fn name() -> &'static str { "OpenGL ES 2.0" }
+    fn kind() -> ApiKind { ApiKind::Gles }
+    fn version() -> Version { Version::new(2, 0) }
+}
+unsafe impl ApiVersion for Gles30 {
+    fn name() -> &'static str { "OpenGL ES 3.0" }
+    fn kind() -> ApiKind { ApiKind::Gles }
+    fn version() -> Version { Version::new(3, 0) }
+}
+
+unsafe impl HasVertexArrays for Gl33 {}
+unsafe impl HasVertexArrays for Gl45 {}
+unsafe impl HasVertexArrays for Gles30 {}
+unsafe impl HasBufferStorage for Gl45 {}
+
+/// A current context, whose api was checked to provide at least the version `V`.
+/// Wrappers of procedures which are not part of every version take it instead of a plain
+/// current context, so the version check happens once instead of at every call.
+/// The check only holds for the context which was current, so the wrappers recheck that it is
+/// still current using check_context.
+/// The version is not a parameter of Api, because the version is only known once a context is
+/// current, and contexts of different versions may share an api.
+pub struct VersionedContext<V: ApiVersion> {
+    current_context_: Rc<CurrentContext>,
+    context_: Weak<Context>,
+    phantom_version_: PhantomData<V>,
+}
+impl<V: ApiVersion> VersionedContext<V> {
+    /// Check the version of the api of the current context.
+    /// Returns Error::Unsupported naming the version if the kind of the api differs or its
+    /// version is too old.
+    pub fn new(current_context: Rc<CurrentContext>) -> Result<Self, Error> {
+        let supported = current_context.api().kind() == V::kind() && current_context.api().info().map_or(false, |info| info.version >= V::version());
+        if supported {
+            Ok(VersionedContext {
+                context_: Rc::downgrade(current_context.context()),
+                current_context_: current_context,
+                phantom_version_: PhantomData,
+            })
+        } else {
+            Err(Error::Unsupported(V::name()))
+        }
+    }
+    /// Get the current context.
+    pub fn current_context(&self) -> &Rc<CurrentContext> {
+        &self.current_context_
+    }
+    /// Returns Error::WrongContext if another context was made current since the version was
+    /// checked.
+    pub fn check_context(&self) -> Result<(), Error> {
+        let is_current = self.context_.upgrade().map_or(false, |context| Rc::ptr_eq(&context, self.current_context().context()));
+        if is_current {
+            Ok(())
+        } else {
+            Err(Error::WrongContext)
+        }
+    }
+}
+impl<V: ApiVersion> Clone for VersionedContext<V> {
+    fn clone(&self) -> Self {
+        VersionedContext {
+            current_context_: self.current_context_.clone(),
+            context_: self.context_.clone(),
+            phantom_version_: PhantomData,
+        }
+    }
+}
+impl<V: ApiVersion> Deref for VersionedContext<V> {
+    type Target = CurrentContext;
+    fn deref(&self) -> &CurrentContext {
+        &self.current_context_
+    }
+}
@@ -0,0 +1,163 @@
+use std::rc::{Rc, Weak};
+
+use gl::types::{GLenum, GLuint};
+
+use Error;
+use ApiKind;
+use ObjectKind;
+
+use Context;
+use ContextId;
+use CurrentContext;
+use SharingGroupId;
+use HasVertexArrays;
+use VersionedContext;
+
+/// Identifier of vertex arrays for glObjectLabel, which is not part of every generated binding.
+const VERTEX_ARRAY_: GLenum = 0x8074;
+
+/// A vertex array object which does not implement Sync or Send, but may be actually used.
+/// Vertex arrays can not be shared between contexts, so it may only be used while the context
+/// it was created in is current.
+pub struct CurrentVertexArray {
+    gl_handle_: GLuint,
+    current_context_: Rc<CurrentContext>,
+    context_: Weak<Context>,
+    context_id_: ContextId,
+    sharing_group_id_: SharingGroupId,
+    label_: Option<String>,
+}
+impl CurrentVertexArray {
+    /// Get the gl handle.
+    pub fn gl_handle(&self) -> GLuint {
+        self.gl_handle_
+    }
+    /// Get the current context.
+    pub fn current_context(&self) -> &Rc<CurrentContext> {
+        &self.current_context_
+    }
+    /// Get the id of the context the vertex array belongs to.
+    pub fn context_id(&self) -> ContextId {
+        self.context_id_
+    }
+    /// Get the id of the sharing group.
+    pub fn sharing_group_id(&self) -> SharingGroupId {
+        self.sharing_group_id_
+    }
+    /// Get the label set by set_label, which is added to the errors of the object.
+    pub fn label(&self) -> Option<&str> {
+        self.label_.as_ref().map(|label| &**label)
+    }
+    /// Check that the context of the vertex array is current and not lost.
+    fn check_context_(&self) -> Result<(), Error> {
+        let is_current = self.context_.upgrade().map_or(false, |context| Rc::ptr_eq(&context, self.current_context().context()));
+        if !is_current {
+            Err(Error::WrongContext.with_object(ObjectKind::VertexArray, self.gl_handle(), self.label()))
+        } else if self.current_context().sharing_group().is_lost() {
+            Err(Error::ContextLost.with_object(ObjectKind::VertexArray, self.gl_handle(), self.label()))
+        } else {
+            Ok(())
+        }
+    }
+    /// Set the label. It is passed to glObjectLabel if KHR_debug is supported.
+    pub fn set_label(&mut self, label: &str) -> Result<(), Error> {
+        try!(self.check_context_());
+        self.current_context().api().objects().set_label_unshared(self.sharing_group_id(), self.context_id(), ObjectKind::VertexArray, self.gl_handle(), label);
+        self.label_ = Some(label.to_string());
+        try!(self.current_context().object_label(VERTEX_ARRAY_, self.gl_handle(), label).map_err(|error| error.with_object(ObjectKind::VertexArray, self.gl_handle(), self.label())));
+        Ok(())
+    }
+    /// Bind the vertex array.
+    /// The element array buffer binding is part of the vertex array, so it is unmarked in the
+    /// state cache.
+    pub fn bind(&self) -> Result<(), Error> {
+        try!(self.check_context_());
+        let current_context = self.current_context();
+        let state_cache = current_context.state_cache();
+        if state_cache.bound_vertex_array_gl_handle.get() != self.gl_handle() {
+            unsafe {
+                current_context.gl().BindVertexArray(self.gl_handle());
+            }
+            try!(current_context.check_errors("glBindVertexArray").map_err(|error| error.with_object(ObjectKind::VertexArray, self.gl_handle(), self.label())));
+            state_cache.bound_vertex_array_gl_handle.set(self.gl_handle());
+            state_cache.bound_element_array_buffer_gl_handle.set(0);
+        }
+        Ok(())
+    }
+    /// Unsafe create current vertex array from gl handle.
+    /// The vertex array belongs to the current context.
+    pub unsafe fn from_gl_handle(gl_handle: GLuint, current_context: Rc<CurrentContext>) -> Self {
+        let sharing_group_id = current_context.sharing_group().id();
+        let context_id = current_context.context().id();
+        current_context.api().objects().register_unshared(sharing_group_id, context_id, ObjectKind::VertexArray, gl_handle);
+        CurrentVertexArray {
+            gl_handle_: gl_handle,
+            context_: Rc::downgrade(current_context.context()),
+            context_id_: context_id,
+            current_context_: current_context,
+            sharing_group_id_: sharing_group_id,
+            label_: None,
+        }
+    }
+    /// Create a new vertex array. The version of the context guarantees that vertex arrays are
+    /// supported.
+    /// Returns Error::WrongContext if another context was made current since the version was
+    /// checked.
+    pub fn create<V: HasVertexArrays>(versioned_context: &VersionedContext<V>) -> Result<Self, Error> {
+        try!(versioned_context.check_context());
+        try!(versioned_context.current_context().check_not_lost());
+        Self::gen_(versioned_context.current_context().clone())
+    }
+    /// Create a new vertex array, checking at runtime whether vertex arrays are supported by
+    /// the version 3.0 of opengl or opengl es, or by ARB_vertex_array_object or
+    /// OES_vertex_array_object.
+    /// Returns Error::Unsupported naming the extension otherwise.
+    pub fn create_checked(current_context: Rc<CurrentContext>) -> Result<Self, Error> {
+        try!(current_context.check_not_lost());
+        let extension = match current_context.api().kind() {
+            ApiKind::Gl => "GL_ARB_vertex_array_object",
+            ApiKind::Gles => "GL_OES_vertex_array_object",
+        };
+        let has_version = current_context.api().info().map_or(false, |info| info.version_at_least(3, 0));
+        if !has_version && !current_context.has_extension(extension) {
+            return Err(Error::Unsupported(extension));
+        }
+        Self::gen_(current_context)
+    }
+    /// Generate a vertex array using glGenVertexArrays.
+    /// The procedures are checked even if the version was, because the bindings may have been
+    /// loaded from a context of another version.
+    fn gen_(current_context: Rc<CurrentContext>) -> Result<Self, Error> {
+        {
+            let gl = current_context.gl();
+            try!(current_context.require_functions(&[("glGenVertexArrays", &gl.GenVertexArrays), ("glBindVertexArray", &gl.BindVertexArray), ("glDeleteVertexArrays", &gl.DeleteVertexArrays)]));
+        }
+        unsafe {
+            let mut gl_handle: GLuint = 0;
+            current_context.gl().GenVertexArrays(1, &mut gl_handle as *mut GLuint);
+            try!(current_context.check_errors("glGenVertexArrays").map_err(|error| error.with_object(ObjectKind::VertexArray, gl_handle, None)));
+            Ok(Self::from_gl_handle(gl_handle, current_context))
+        }
+    }
+}
+impl Drop for CurrentVertexArray {
+    fn drop(&mut self) {
+        unsafe {
+            // The vertex array can not be deleted while another context is current, so it stays
+            // registered and is reported as leak.
+            if self.gl_handle() != 0 && self.current_context().gl().DeleteVertexArrays.is_loaded() && self.check_context_().is_ok() {
+                let state_cache = self.current_context().state_cache();
+                if state_cache.bound_vertex_array_gl_handle.get() == self.gl_handle() {
+                    state_cache.bound_vertex_array_gl_handle.set(0);
+                    state_cache.bound_element_array_buffer_gl_handle.set(0);
+                }
+                self.current_context().gl().DeleteVertexArrays(1, &self.gl_handle() as *const GLuint);
+                // Errors can not be propagated out of drop, so they are passed to the error hook.
+                if let Err(error) = self.current_context().check_errors("glDeleteVertexArrays") {
+                    self.current_context().api().report_drop_error("glDeleteVertexArrays", error);
+                }
+                self.current_context().api().objects().unregister_unshared(self.sharing_group_id(), self.context_id(), ObjectKind::VertexArray, self.gl_handle());
+            }
+        }
+    }
+}
@@ -1,25 +1,16 @@
-use std::borrow::Borrow;
-
 use gl;
-use WithMakeCurrent;
-
-use ContextState;
-
-use HandleBorrow;
 
+use ShaderStage;
 use ShaderType;
-use ShaderHandleSpecifier;
-use ShaderHandle;
-use ShaderFacade;
-use Shader;
+use CurrentShader;
 
 pub enum VertexShaderType {}
 unsafe impl ShaderType for VertexShaderType {
     fn enum_val() -> gl::types::GLenum {
         gl::VERTEX_SHADER
     }
+    fn stage() -> ShaderStage {
+        ShaderStage::Vertex
+    }
 }
-pub type VertexShaderHandleSpecifier = ShaderHandleSpecifier<VertexShaderType>;
-pub type VertexShaderHandle = ShaderHandle<VertexShaderType>;
-pub type VertexShaderFacade<HB: HandleBorrow<VertexShaderHandleSpecifier>, WMC: WithMakeCurrent> = ShaderFacade<VertexShaderType, HB, WMC>;
-pub type VertexShader<WMC: WithMakeCurrent> = Shader<VertexShaderType, WMC>;
+pub type CurrentVertexShader = CurrentShader<VertexShaderType>;